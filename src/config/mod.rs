@@ -0,0 +1,536 @@
+use crate::datastore::tax::VatRates;
+use serde::Deserialize;
+use std::fmt;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::Level;
+
+pub const USAGE: &str = "\
+Usage: joi [OPTIONS]
+
+Options:
+  -c, --config <PATH>                 TOML file to read settings from [env: JOI_CONFIG]
+      --catalogue <PATH>              TOML or JSON file of accounts and price plans [env: JOI_CATALOGUE]
+      --demo                          Use the built-in demo accounts and price plans [env: JOI_DEMO]
+      --bind <ADDRESS>                Address to listen on [env: JOI_BIND] [default: 0.0.0.0]
+  -p, --port <PORT>                   Port to listen on [env: JOI_PORT] [default: 8080]
+      --storage <BACKEND>             Storage backend, `memory` or `file` [env: JOI_STORAGE] [default: memory]
+      --data-file <PATH>              Log file used by the `file` backend [env: JOI_DATA_FILE]
+      --seed, --no-seed               Whether to seed random readings with --demo [env: JOI_SEED] [default: seed]
+      --log-level <LEVEL>             One of error, warn, info, debug, trace [env: JOI_LOG_LEVEL] [default: info]
+      --max-body-bytes <BYTES>        Largest request body accepted [env: JOI_MAX_BODY_BYTES] [default: 2097152]
+      --max-readings <COUNT>          Most readings accepted in one request [env: JOI_MAX_READINGS] [default: 10000]
+      --vat-rate <RATE>               Standard VAT rate, as a fraction [env: JOI_VAT_RATE] [default: 0.2]
+      --domestic-vat-rate <RATE>      Reduced VAT rate for domestic energy [env: JOI_DOMESTIC_VAT_RATE] [default: 0.05]
+  -h, --help                          Print this help
+
+Settings are taken from the command line first, then the environment, then the
+config file, then the defaults.
+";
+
+/// Where readings are kept
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StorageBackend {
+    /// In memory, lost when the application stops
+    Memory,
+    /// In an append-only log file that is replayed on startup
+    File(PathBuf),
+}
+
+/// Where the accounts and price plans come from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CatalogueSource {
+    /// The built-in demo dataset
+    Demo,
+    /// A TOML or JSON catalogue file
+    File(PathBuf),
+}
+
+/// Bounds on what a single request may contain
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RequestLimits {
+    pub max_body_bytes: usize,
+    pub max_readings_per_request: usize,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: 2 * 1024 * 1024,
+            max_readings_per_request: 10_000,
+        }
+    }
+}
+
+/// Runtime settings for the `joi` binary
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    pub bind_address: IpAddr,
+    pub port: u16,
+    pub storage: StorageBackend,
+    pub catalogue: CatalogueSource,
+    pub seed: bool,
+    pub log_level: Level,
+    pub limits: RequestLimits,
+    pub vat_rates: VatRates,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind_address: IpAddr::from([0, 0, 0, 0]),
+            port: 8080,
+            storage: StorageBackend::Memory,
+            catalogue: CatalogueSource::Demo,
+            seed: true,
+            log_level: Level::INFO,
+            limits: RequestLimits::default(),
+            vat_rates: VatRates::default(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ConfigError {
+    /// `--help` was passed, so the usage should be printed instead of running
+    HelpRequested,
+    /// A flag or environment variable was not understood
+    InvalidArgument(String),
+    /// The config file could not be read or parsed
+    InvalidFile(PathBuf, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::HelpRequested => write!(f, "help requested"),
+            Self::InvalidArgument(message) => write!(f, "{message}"),
+            Self::InvalidFile(path, message) => {
+                write!(f, "invalid config file {}: {message}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Settings from a single source, where anything left out falls through to the
+/// next source in order of precedence
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct PartialConfig {
+    bind_address: Option<IpAddr>,
+    port: Option<u16>,
+    storage: Option<String>,
+    data_file: Option<PathBuf>,
+    catalogue: Option<PathBuf>,
+    demo: Option<bool>,
+    seed: Option<bool>,
+    log_level: Option<String>,
+    max_body_bytes: Option<usize>,
+    max_readings_per_request: Option<usize>,
+    vat_rate: Option<f64>,
+    domestic_vat_rate: Option<f64>,
+}
+
+impl PartialConfig {
+    /// Fills the settings missing here from `fallback`
+    ///
+    /// The storage backend and data file are taken as a pair, since a data
+    /// file only means anything to the file backend: choosing `memory` here
+    /// drops a data file from `fallback`, and a data file here chooses the
+    /// file backend over a `storage` from `fallback`.
+    fn or(self, fallback: Self) -> Self {
+        let (storage, data_file) = match (self.storage, self.data_file) {
+            (Some(storage), data_file) if storage == "memory" => (Some(storage), data_file),
+            (None, Some(data_file)) => (None, Some(data_file)),
+            (storage, data_file) => (
+                storage.or(fallback.storage),
+                data_file.or(fallback.data_file),
+            ),
+        };
+        Self {
+            bind_address: self.bind_address.or(fallback.bind_address),
+            port: self.port.or(fallback.port),
+            storage,
+            data_file,
+            catalogue: self.catalogue.or(fallback.catalogue),
+            demo: self.demo.or(fallback.demo),
+            seed: self.seed.or(fallback.seed),
+            log_level: self.log_level.or(fallback.log_level),
+            max_body_bytes: self.max_body_bytes.or(fallback.max_body_bytes),
+            max_readings_per_request: self
+                .max_readings_per_request
+                .or(fallback.max_readings_per_request),
+            vat_rate: self.vat_rate.or(fallback.vat_rate),
+            domestic_vat_rate: self.domestic_vat_rate.or(fallback.domestic_vat_rate),
+        }
+    }
+
+    fn from_args(
+        args: impl IntoIterator<Item = String>,
+    ) -> Result<(Self, Option<PathBuf>), ConfigError> {
+        let mut partial = Self::default();
+        let mut config_file = None;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => {
+                    (flag.to_string(), Some(value.to_string()))
+                }
+                _ => (arg, None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| ConfigError::InvalidArgument(format!("{flag} needs a value")))
+            };
+            match flag.as_str() {
+                "-h" | "--help" => return Err(ConfigError::HelpRequested),
+                "-c" | "--config" => config_file = Some(PathBuf::from(value()?)),
+                "--bind" => partial.bind_address = Some(parse(&flag, &value()?)?),
+                "-p" | "--port" => partial.port = Some(parse(&flag, &value()?)?),
+                "--storage" => partial.storage = Some(value()?),
+                "--data-file" => partial.data_file = Some(PathBuf::from(value()?)),
+                "--catalogue" => partial.catalogue = Some(PathBuf::from(value()?)),
+                "--demo" => partial.demo = Some(true),
+                "--seed" => partial.seed = Some(true),
+                "--no-seed" => partial.seed = Some(false),
+                "--log-level" => partial.log_level = Some(value()?),
+                "--max-body-bytes" => partial.max_body_bytes = Some(parse(&flag, &value()?)?),
+                "--max-readings" => {
+                    partial.max_readings_per_request = Some(parse(&flag, &value()?)?)
+                }
+                "--vat-rate" => partial.vat_rate = Some(parse(&flag, &value()?)?),
+                "--domestic-vat-rate" => partial.domestic_vat_rate = Some(parse(&flag, &value()?)?),
+                _ => {
+                    return Err(ConfigError::InvalidArgument(format!(
+                        "unexpected argument {flag}"
+                    )))
+                }
+            }
+        }
+        Ok((partial, config_file))
+    }
+
+    fn from_env(
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<(Self, Option<PathBuf>), ConfigError> {
+        let var = |name: &str| env(name).filter(|value| !value.is_empty());
+        let partial = Self {
+            bind_address: parse_var(var, "JOI_BIND")?,
+            port: parse_var(var, "JOI_PORT")?,
+            storage: var("JOI_STORAGE"),
+            data_file: var("JOI_DATA_FILE").map(PathBuf::from),
+            catalogue: var("JOI_CATALOGUE").map(PathBuf::from),
+            demo: parse_var(var, "JOI_DEMO")?,
+            seed: parse_var(var, "JOI_SEED")?,
+            log_level: var("JOI_LOG_LEVEL"),
+            max_body_bytes: parse_var(var, "JOI_MAX_BODY_BYTES")?,
+            max_readings_per_request: parse_var(var, "JOI_MAX_READINGS")?,
+            vat_rate: parse_var(var, "JOI_VAT_RATE")?,
+            domestic_vat_rate: parse_var(var, "JOI_DOMESTIC_VAT_RATE")?,
+        };
+        Ok((partial, var("JOI_CONFIG").map(PathBuf::from)))
+    }
+
+    fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let invalid = |message: String| ConfigError::InvalidFile(path.to_path_buf(), message);
+        let contents = std::fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+        toml::from_str(&contents).map_err(|e| invalid(e.message().to_string()))
+    }
+
+    fn resolve(self) -> Result<Config, ConfigError> {
+        let defaults = Config::default();
+        let storage = match (self.storage.as_deref(), self.data_file) {
+            (None | Some("memory"), None) => StorageBackend::Memory,
+            (None | Some("file"), Some(path)) => StorageBackend::File(path),
+            (Some("file"), None) => {
+                return Err(ConfigError::InvalidArgument(
+                    "the file storage backend needs a data file".to_string(),
+                ))
+            }
+            (Some("memory"), Some(_)) => {
+                return Err(ConfigError::InvalidArgument(
+                    "a data file cannot be used with the memory storage backend".to_string(),
+                ))
+            }
+            (Some(other), _) => {
+                return Err(ConfigError::InvalidArgument(format!(
+                    "unknown storage backend {other}, expected memory or file"
+                )))
+            }
+        };
+        let catalogue = match (self.catalogue, self.demo.unwrap_or(false)) {
+            (Some(path), false) => CatalogueSource::File(path),
+            (None, true) => CatalogueSource::Demo,
+            (Some(_), true) => {
+                return Err(ConfigError::InvalidArgument(
+                    "a catalogue cannot be used together with the demo dataset".to_string(),
+                ))
+            }
+            (None, false) => {
+                return Err(ConfigError::InvalidArgument(
+                    "no catalogue given, pass --catalogue <PATH> or --demo".to_string(),
+                ))
+            }
+        };
+        let log_level = match self.log_level {
+            Some(level) => parse("log level", &level)?,
+            None => defaults.log_level,
+        };
+        let vat_rates = VatRates {
+            standard: vat_rate("VAT rate", self.vat_rate, defaults.vat_rates.standard)?,
+            domestic: vat_rate(
+                "domestic VAT rate",
+                self.domestic_vat_rate,
+                defaults.vat_rates.domestic,
+            )?,
+        };
+
+        Ok(Config {
+            bind_address: self.bind_address.unwrap_or(defaults.bind_address),
+            port: self.port.unwrap_or(defaults.port),
+            storage,
+            catalogue,
+            seed: self.seed.unwrap_or(defaults.seed),
+            log_level,
+            limits: RequestLimits {
+                max_body_bytes: self
+                    .max_body_bytes
+                    .unwrap_or(defaults.limits.max_body_bytes),
+                max_readings_per_request: self
+                    .max_readings_per_request
+                    .unwrap_or(defaults.limits.max_readings_per_request),
+            },
+            vat_rates,
+        })
+    }
+}
+
+impl Config {
+    /// Builds the configuration from command-line arguments, environment
+    /// variables and an optional config file, in that order of precedence
+    ///
+    /// # Arguments
+    /// * `args` - Command-line arguments, without the program name
+    /// * `env` - Looks up an environment variable by name
+    pub fn load(
+        args: impl IntoIterator<Item = String>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigError> {
+        let (from_args, args_config_file) = PartialConfig::from_args(args)?;
+        let (from_env, env_config_file) = PartialConfig::from_env(env)?;
+        let from_file = match args_config_file.or(env_config_file) {
+            Some(path) => PartialConfig::from_file(&path)?,
+            None => PartialConfig::default(),
+        };
+
+        from_args.or(from_env).or(from_file).resolve()
+    }
+
+    /// Builds the configuration for this process
+    pub fn from_process() -> Result<Self, ConfigError> {
+        Self::load(std::env::args().skip(1), |name| std::env::var(name).ok())
+    }
+}
+
+fn parse<T: FromStr>(name: &str, value: &str) -> Result<T, ConfigError> {
+    value
+        .parse()
+        .map_err(|_| ConfigError::InvalidArgument(format!("invalid value {value:?} for {name}")))
+}
+
+/// Checks a VAT rate is a fraction between 0 and 1
+fn vat_rate(name: &str, rate: Option<f64>, default: f64) -> Result<f64, ConfigError> {
+    match rate {
+        Some(rate) if !(0.0..=1.0).contains(&rate) => Err(ConfigError::InvalidArgument(format!(
+            "invalid {name} {rate}, expected a fraction between 0 and 1"
+        ))),
+        Some(rate) => Ok(rate),
+        None => Ok(default),
+    }
+}
+
+fn parse_var<T: FromStr>(
+    var: impl Fn(&str) -> Option<String>,
+    name: &str,
+) -> Result<Option<T>, ConfigError> {
+    var(name).map(|value| parse(name, &value)).transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect::<HashMap<String, String>>();
+        move |name| vars.get(name).cloned()
+    }
+
+    fn config_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("joi-{}-{}.toml", name, std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config = Config::load(args(&["--demo"]), env(&[])).unwrap();
+
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn test_command_line_flags() {
+        let config = Config::load(
+            args(&[
+                "--catalogue",
+                "plans.toml",
+                "--bind",
+                "127.0.0.1",
+                "--port=9090",
+                "--data-file",
+                "joi.log",
+                "--no-seed",
+                "--log-level",
+                "debug",
+                "--max-readings",
+                "50",
+            ]),
+            env(&[]),
+        )
+        .unwrap();
+
+        assert_eq!(config.bind_address, IpAddr::from([127, 0, 0, 1]));
+        assert_eq!(config.port, 9090);
+        assert_eq!(
+            config.catalogue,
+            CatalogueSource::File(PathBuf::from("plans.toml"))
+        );
+        assert_eq!(
+            config.storage,
+            StorageBackend::File(PathBuf::from("joi.log"))
+        );
+        assert!(!config.seed);
+        assert_eq!(config.log_level, Level::DEBUG);
+        assert_eq!(config.limits.max_readings_per_request, 50);
+    }
+
+    #[test]
+    fn test_precedence() {
+        let path = config_file(
+            "precedence",
+            "demo = true\nport = 7000\nbind_address = \"10.0.0.1\"\nseed = false\n",
+        );
+        let path_str = path.to_str().unwrap();
+
+        let config = Config::load(
+            args(&["--port", "9000"]),
+            env(&[
+                ("JOI_CONFIG", path_str),
+                ("JOI_PORT", "8000"),
+                ("JOI_BIND", "10.0.0.2"),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(config.port, 9000);
+        assert_eq!(config.bind_address, IpAddr::from([10, 0, 0, 2]));
+        assert!(!config.seed);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_storage_choice_takes_precedence_with_its_data_file() {
+        let path = config_file("storage", "demo = true\ndata_file = \"file.log\"\n");
+        let path_str = path.to_str().unwrap();
+
+        let load = |flags: &[&str], vars: &[(&str, &str)]| {
+            let mut vars = vars.to_vec();
+            vars.push(("JOI_CONFIG", path_str));
+            Config::load(args(flags), env(&vars)).unwrap().storage
+        };
+
+        assert_eq!(
+            load(&[], &[]),
+            StorageBackend::File(PathBuf::from("file.log"))
+        );
+        assert_eq!(load(&["--storage", "memory"], &[]), StorageBackend::Memory);
+        assert_eq!(
+            load(&["--storage", "memory"], &[("JOI_DATA_FILE", "env.log")]),
+            StorageBackend::Memory
+        );
+        assert_eq!(
+            load(&["--data-file", "cli.log"], &[("JOI_STORAGE", "memory")]),
+            StorageBackend::File(PathBuf::from("cli.log"))
+        );
+        assert_eq!(
+            load(&["--storage", "file"], &[("JOI_DATA_FILE", "env.log")]),
+            StorageBackend::File(PathBuf::from("env.log"))
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_help() {
+        assert_eq!(
+            Config::load(args(&["--help"]), env(&[])),
+            Err(ConfigError::HelpRequested)
+        );
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        let demo = env(&[("JOI_DEMO", "true")]);
+        assert!(Config::load(args(&["--port", "eighty"]), &demo).is_err());
+        assert!(Config::load(args(&["--unknown"]), &demo).is_err());
+        assert!(Config::load(args(&["--port"]), &demo).is_err());
+        assert!(Config::load(args(&["--storage", "file"]), &demo).is_err());
+        assert!(Config::load(args(&[]), env(&[("JOI_LOG_LEVEL", "loud")])).is_err());
+        assert!(Config::load(args(&["--vat-rate", "20"]), &demo).is_err());
+    }
+
+    #[test]
+    fn test_vat_rates() {
+        let config = Config::load(
+            args(&["--demo", "--vat-rate", "0.21"]),
+            env(&[("JOI_DOMESTIC_VAT_RATE", "0")]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.vat_rates,
+            VatRates {
+                standard: 0.21,
+                domestic: 0.0
+            }
+        );
+    }
+
+    #[test]
+    fn test_catalogue_is_required() {
+        assert!(Config::load(args(&[]), env(&[])).is_err());
+        assert!(Config::load(args(&["--demo", "--catalogue", "plans.toml"]), env(&[])).is_err());
+    }
+
+    #[test]
+    fn test_unknown_config_file_setting() {
+        let path = config_file("unknown", "colour = \"blue\"\n");
+
+        let result = Config::load(args(&["--config", path.to_str().unwrap()]), env(&[]));
+
+        assert!(matches!(result, Err(ConfigError::InvalidFile(_, _))));
+        std::fs::remove_file(path).unwrap();
+    }
+}
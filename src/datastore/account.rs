@@ -1,15 +1,218 @@
-#[derive(Debug)]
+use crate::datastore::consumption::Consumption;
+use crate::datastore::money::{Currency, Rounding};
+use crate::datastore::plan::{CostBreakdown, PricePlan};
+use crate::datastore::tax::VatRates;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(from = "StoredAccount")]
 pub struct Account {
-    pub price_plan_id: String,
-    #[allow(dead_code)]
     pub user: String,
+    /// Every plan the account has been put on, in the order they took effect
+    ///
+    /// The first entry has no start and covers everything before the second.
+    pub price_plan_history: Vec<PlanAssignment>,
+}
+
+/// An account as it has been stored, including the shape from before accounts
+/// kept a history, when they held a single `price_plan_id`
+#[derive(Deserialize)]
+struct StoredAccount {
+    user: String,
+    #[serde(default)]
+    price_plan_history: Vec<PlanAssignment>,
+    price_plan_id: Option<String>,
+}
+
+impl From<StoredAccount> for Account {
+    fn from(stored: StoredAccount) -> Self {
+        match stored.price_plan_id {
+            Some(price_plan_id) if stored.price_plan_history.is_empty() => {
+                Self::new(&price_plan_id, &stored.user)
+            }
+            _ => Self {
+                user: stored.user,
+                price_plan_history: stored.price_plan_history,
+            },
+        }
+    }
+}
+
+/// A price plan an account is on from `effective_from` until the next assignment
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PlanAssignment {
+    pub price_plan_id: String,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub effective_from: Option<OffsetDateTime>,
+}
+
+/// The span of time an account spent on one price plan
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlanPeriod<'a> {
+    pub price_plan_id: &'a str,
+    pub from: Option<OffsetDateTime>,
+    pub to: Option<OffsetDateTime>,
 }
 
 impl Account {
     pub fn new(price_plan_id: &str, user: &str) -> Self {
         Self {
-            price_plan_id: price_plan_id.to_string(),
             user: user.to_string(),
+            price_plan_history: vec![PlanAssignment {
+                price_plan_id: price_plan_id.to_string(),
+                effective_from: None,
+            }],
         }
     }
+
+    /// Returns the id of the plan the account is on at the given instant
+    pub fn price_plan_at(&self, instant: OffsetDateTime) -> &str {
+        self.price_plan_history
+            .iter()
+            .rev()
+            .find(|assignment| assignment.effective_from.is_none_or(|from| from <= instant))
+            .or(self.price_plan_history.first())
+            .map_or("", |assignment| assignment.price_plan_id.as_str())
+    }
+
+    /// Returns when the most recent switch of plan takes effect, if there was one
+    pub fn last_switch(&self) -> Option<OffsetDateTime> {
+        self.price_plan_history
+            .last()
+            .and_then(|assignment| assignment.effective_from)
+    }
+
+    /// Moves the account onto another plan from the given instant, keeping the
+    /// plans it was on before
+    pub fn switch_price_plan(&mut self, price_plan_id: &str, effective_from: OffsetDateTime) {
+        self.price_plan_history.push(PlanAssignment {
+            price_plan_id: price_plan_id.to_string(),
+            effective_from: Some(effective_from),
+        });
+    }
+
+    /// Splits the account's history into the periods spent on each plan
+    pub fn plan_periods(&self) -> Vec<PlanPeriod<'_>> {
+        self.price_plan_history
+            .iter()
+            .enumerate()
+            .map(|(index, assignment)| PlanPeriod {
+                price_plan_id: &assignment.price_plan_id,
+                from: assignment.effective_from,
+                to: self
+                    .price_plan_history
+                    .get(index + 1)
+                    .and_then(|next| next.effective_from),
+            })
+            .collect()
+    }
+
+    /// Calculates what a consumption cost on the plans the account was on at the time
+    ///
+    /// # Arguments
+    /// * `consumption` - Energy consumption integrated from the account's readings
+    /// * `price_plans` - The price plans the account's history refers to
+    /// * `rounding` - How each period's total is rounded
+    /// * `vat_rates` - The VAT rates to tax each period at
+    ///
+    /// # Returns
+    /// The cost of each period on a plan, in chronological order, with the
+    /// consumption it covers. Periods on a plan that cannot be found cost nothing.
+    pub fn costs_by_period<'a>(
+        &'a self,
+        consumption: &Consumption,
+        price_plans: &[PricePlan],
+        rounding: Rounding,
+        vat_rates: &VatRates,
+    ) -> Vec<(PlanPeriod<'a>, Consumption, CostBreakdown)> {
+        self.plan_periods()
+            .into_iter()
+            .map(|period| {
+                let used = consumption.between(period.from, period.to);
+                let cost = price_plans
+                    .iter()
+                    .find(|price_plan| price_plan.supplier_id == period.price_plan_id)
+                    .map_or(CostBreakdown::zero(Currency::default()), |price_plan| {
+                        price_plan.cost_breakdown(&used, rounding, vat_rates)
+                    });
+                (period, used, cost)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datastore::consumption::IntegrationMethod;
+    use crate::datastore::money::Money;
+    use crate::datastore::reading::ElectricityReading;
+    use std::collections::HashMap;
+    use time::macros::datetime;
+
+    fn switched_account() -> Account {
+        let mut account = Account::new("plan-1", "user-1");
+        account.switch_price_plan("plan-2", datetime!(2020-11-29 09:00:00 UTC));
+        account
+    }
+
+    #[test]
+    fn test_price_plan_at() {
+        let account = switched_account();
+
+        assert_eq!(
+            account.price_plan_at(datetime!(2020-11-29 08:59:59 UTC)),
+            "plan-1"
+        );
+        assert_eq!(
+            account.price_plan_at(datetime!(2020-11-29 09:00:00 UTC)),
+            "plan-2"
+        );
+        assert_eq!(
+            account.price_plan_at(datetime!(2030-01-01 00:00:00 UTC)),
+            "plan-2"
+        );
+    }
+
+    #[test]
+    fn test_account_without_history_is_read_as_one_plan() {
+        let account: Account =
+            serde_json::from_str(r#"{"price_plan_id": "plan-1", "user": "user-1"}"#).unwrap();
+
+        assert_eq!(account, Account::new("plan-1", "user-1"));
+    }
+
+    #[test]
+    fn test_costs_by_period() {
+        let account = switched_account();
+        let readings = vec![
+            ElectricityReading::new(datetime!(2020-11-29 08:00:00 UTC), 1.0),
+            ElectricityReading::new(datetime!(2020-11-29 10:00:00 UTC), 1.0),
+        ];
+        let consumption = Consumption::from_readings(&readings, IntegrationMethod::Trapezoidal);
+        let price_plans = vec![
+            PricePlan::new("plan-1", "plan-1", 10.0, HashMap::new()),
+            PricePlan::new("plan-2", "plan-2", 2.0, HashMap::new()),
+        ];
+
+        let costs = account
+            .costs_by_period(
+                &consumption,
+                &price_plans,
+                Rounding::PerLine,
+                &VatRates::default(),
+            )
+            .into_iter()
+            .map(|(period, used, cost)| (period.price_plan_id, used.total_kwh(), cost.total))
+            .collect::<Vec<(&str, f64, Money)>>();
+
+        assert_eq!(
+            costs,
+            vec![
+                ("plan-1", 1.0, Money::from_minor_units(1000, Currency::Gbp)),
+                ("plan-2", 1.0, Money::from_minor_units(200, Currency::Gbp))
+            ]
+        );
+    }
 }
@@ -0,0 +1,449 @@
+use crate::datastore::account::Account;
+use crate::datastore::consumption::Consumption;
+use crate::datastore::money::{Currency, Money, Rounding};
+use crate::datastore::plan::{LocalTime, PricePlan};
+use crate::datastore::tax::VatRates;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use time::{Date, Duration, Month, OffsetDateTime, Time, UtcOffset};
+
+/// A calendar month that a bill covers, written as `YYYY-MM`
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct BillingPeriod {
+    pub year: i32,
+    pub month: Month,
+}
+
+impl BillingPeriod {
+    pub fn new(year: i32, month: Month) -> Self {
+        Self { year, month }
+    }
+
+    /// Returns the first instant of the month in the given local time
+    pub fn start(self, local_time: LocalTime) -> OffsetDateTime {
+        first_of_month(self.year, self.month, local_time)
+    }
+
+    /// Returns the first instant after the month in the given local time
+    pub fn end(self, local_time: LocalTime) -> OffsetDateTime {
+        let (year, month) = self.following();
+        first_of_month(year, month, local_time)
+    }
+
+    /// Returns the year and month after this one
+    fn following(self) -> (i32, Month) {
+        match self.month {
+            Month::December => (self.year + 1, Month::January),
+            month => (self.year, month.next()),
+        }
+    }
+}
+
+fn first_of_month(year: i32, month: Month, local_time: LocalTime) -> OffsetDateTime {
+    let date =
+        Date::from_calendar_date(year, month, 1).expect("the first of a month is a valid date");
+    local_time.at(date, Time::MIDNIGHT)
+}
+
+impl TryFrom<String> for BillingPeriod {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let invalid = || format!("invalid billing period {value:?}, expected YYYY-MM");
+        let (year, month) = value.split_once('-').ok_or_else(invalid)?;
+        if year.len() != 4 || month.len() != 2 {
+            return Err(invalid());
+        }
+        let year = year.parse::<i32>().map_err(|_| invalid())?;
+        let month = month
+            .parse::<u8>()
+            .ok()
+            .and_then(|month| Month::try_from(month).ok())
+            .ok_or_else(invalid)?;
+        let period = Self::new(year, month);
+        let (next_year, next_month) = period.following();
+        if Date::from_calendar_date(next_year, next_month, 1).is_err() {
+            return Err(format!("billing period {value:?} is too far in the future"));
+        }
+        Ok(period)
+    }
+}
+
+impl From<BillingPeriod> for String {
+    fn from(period: BillingPeriod) -> Self {
+        period.to_string()
+    }
+}
+
+impl fmt::Display for BillingPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}", self.year, u8::from(self.month))
+    }
+}
+
+/// What a line of a bill charges or credits for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineKind {
+    Consumption,
+    StandingCharge,
+    ExportCredit,
+}
+
+/// The unit a line's quantity is measured in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum Unit {
+    #[serde(rename = "kWh")]
+    Kwh,
+    #[serde(rename = "days")]
+    Days,
+}
+
+/// One charge or credit on a bill
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BillLine {
+    pub price_plan_id: String,
+    pub kind: LineKind,
+    /// The rate band of a consumption line, e.g. `off-peak` or `tier 1`
+    pub description: String,
+    pub quantity: f64,
+    pub unit: Unit,
+    /// The amount rounded to the currency's minor unit, negative for credits
+    pub amount: Money,
+}
+
+/// The VAT charged on the time a bill spends on one price plan
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TaxLine {
+    pub price_plan_id: String,
+    pub rate: f64,
+    /// The consumption and standing charges the VAT is charged on
+    pub taxable: Money,
+    pub amount: Money,
+}
+
+/// An itemised bill for a meter's consumption over a calendar month, on the
+/// plans its account was on at the time
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Bill {
+    pub period: BillingPeriod,
+    #[serde(with = "time::serde::rfc3339")]
+    pub from: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub to: OffsetDateTime,
+    pub currency: Currency,
+    pub consumption_kwh: f64,
+    pub export_kwh: f64,
+    pub lines: Vec<BillLine>,
+    pub taxes: Vec<TaxLine>,
+    /// The lines added up, before tax
+    pub subtotal: Money,
+    pub tax: Money,
+    /// The subtotal with VAT added
+    pub total: Money,
+}
+
+impl Bill {
+    /// Bills a meter's consumption over a calendar month
+    ///
+    /// The month runs from midnight to midnight in the local time of the plan
+    /// the account was on when it began. Each part of the month is billed on
+    /// the plan the account was on, with a consumption line for every rate
+    /// band used, a standing charge for every day spent on the plan, whatever
+    /// readings there are, and a credit for any energy exported. VAT is charged on each plan's
+    /// consumption and standing charges at the plan's rate.
+    ///
+    /// # Arguments
+    /// * `account` - The account the meter is registered to
+    /// * `consumption` - Energy consumption integrated from the meter's readings
+    /// * `price_plans` - The price plans the account's history refers to
+    /// * `period` - The month to bill
+    /// * `rounding` - Whether totals add up the rounded lines or round the exact amounts
+    /// * `vat_rates` - The VAT rates in force
+    ///
+    /// # Returns
+    /// The bill, or `None` when the account's plans during the month are in
+    /// different currencies. Time on a plan that cannot be found is not billed.
+    pub fn generate(
+        account: &Account,
+        consumption: &Consumption,
+        price_plans: &[PricePlan],
+        period: BillingPeriod,
+        rounding: Rounding,
+        vat_rates: &VatRates,
+    ) -> Option<Self> {
+        let find_plan = |price_plan_id: &str| {
+            price_plans
+                .iter()
+                .find(|price_plan| price_plan.supplier_id == price_plan_id)
+        };
+        let utc = LocalTime::Offset(UtcOffset::UTC);
+        let local_time = find_plan(account.price_plan_at(period.start(utc)))
+            .map_or(utc, |price_plan| price_plan.local_time);
+        let from = period.start(local_time);
+        let to = period.end(local_time);
+        let used = consumption.between(Some(from), Some(to));
+
+        let billed = account
+            .plan_periods()
+            .into_iter()
+            .filter_map(|plan_period| {
+                let start = plan_period.from.map_or(from, |start| start.max(from));
+                let end = plan_period.to.map_or(to, |end| end.min(to));
+                let price_plan = find_plan(plan_period.price_plan_id)?;
+                let used_on_plan = used.between(Some(start), Some(end));
+                (start < end).then_some((price_plan, start, end, used_on_plan))
+            })
+            .collect::<Vec<(&PricePlan, OffsetDateTime, OffsetDateTime, Consumption)>>();
+        let currency = billed
+            .first()
+            .map_or(Currency::default(), |(price_plan, ..)| price_plan.currency);
+        if billed
+            .iter()
+            .any(|(price_plan, ..)| price_plan.currency != currency)
+        {
+            return None;
+        }
+
+        let mut lines = Vec::new();
+        let mut amounts = Vec::new();
+        let mut taxes = Vec::new();
+        for (price_plan, start, end, used_on_plan) in billed {
+            let mut line = |kind, description: String, quantity, unit, amount: Money| {
+                amounts.push(amount);
+                lines.push(BillLine {
+                    price_plan_id: price_plan.supplier_id.clone(),
+                    kind,
+                    description,
+                    quantity,
+                    unit,
+                    amount: amount.round(),
+                });
+            };
+            let mut taxable = Vec::new();
+            for charge in price_plan.energy_charges(&used_on_plan) {
+                taxable.push(charge.cost);
+                line(
+                    LineKind::Consumption,
+                    charge.rate.to_string(),
+                    charge.energy_kwh,
+                    Unit::Kwh,
+                    charge.cost,
+                );
+            }
+            let standing_charge = price_plan.standing_charge_between(start, end);
+            taxable.push(standing_charge);
+            line(
+                LineKind::StandingCharge,
+                "standing charge".to_string(),
+                (end - start).as_seconds_f64() / Duration::DAY.as_seconds_f64(),
+                Unit::Days,
+                standing_charge,
+            );
+            let export_kwh = used_on_plan.total_export_kwh();
+            if export_kwh > 0.0 {
+                line(
+                    LineKind::ExportCredit,
+                    "export".to_string(),
+                    export_kwh,
+                    Unit::Kwh,
+                    -price_plan.export_credit(&used_on_plan),
+                );
+            }
+
+            let taxable = rounding.total(currency, taxable);
+            let rate = vat_rates.rate_for(price_plan.vat_category);
+            taxes.push(TaxLine {
+                price_plan_id: price_plan.supplier_id.clone(),
+                rate,
+                taxable,
+                amount: (taxable * rate).round(),
+            });
+        }
+
+        let subtotal = rounding.total(currency, amounts);
+        let tax = Money::sum(currency, taxes.iter().map(|tax| tax.amount));
+        Some(Self {
+            period,
+            from,
+            to,
+            currency,
+            consumption_kwh: used.total_kwh(),
+            export_kwh: used.total_export_kwh(),
+            lines,
+            taxes,
+            subtotal,
+            tax,
+            total: subtotal + tax,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datastore::consumption::IntegrationMethod;
+    use crate::datastore::plan::RateBand;
+    use crate::datastore::reading::ElectricityReading;
+    use std::collections::HashMap;
+    use time::macros::{datetime, offset, time};
+
+    fn gbp(amount: f64) -> Money {
+        Money::from_f64(amount, Currency::Gbp)
+    }
+
+    fn period(value: &str) -> Result<BillingPeriod, String> {
+        BillingPeriod::try_from(value.to_string())
+    }
+
+    fn economy_seven_plan() -> PricePlan {
+        PricePlan::new("plan-e7", "Economy 7", 0.3, HashMap::new())
+            .with_rate_bands(
+                UtcOffset::UTC,
+                vec![RateBand::new("off-peak", time!(00:00), time!(07:00), 0.1)],
+            )
+            .with_standing_charge(0.48)
+    }
+
+    fn consumption(readings: &[ElectricityReading]) -> Consumption {
+        Consumption::from_readings(readings, IntegrationMethod::Trapezoidal)
+    }
+
+    #[test]
+    fn test_billing_period_is_a_calendar_month() {
+        let december = period("2020-12").unwrap();
+
+        assert_eq!(december, BillingPeriod::new(2020, Month::December));
+        assert_eq!(december.to_string(), "2020-12");
+        assert_eq!(
+            december.start(UtcOffset::UTC.into()),
+            datetime!(2020-12-01 00:00 UTC)
+        );
+        assert_eq!(
+            december.end(offset!(+1).into()),
+            datetime!(2021-01-01 00:00 +01:00)
+        );
+        assert!(period("2020-13").is_err());
+        assert!(period("2020-1").is_err());
+        assert!(period("December").is_err());
+        assert!(period("9999-12").is_err());
+    }
+
+    #[test]
+    fn test_bill_itemises_rate_bands() {
+        let account = Account::new("plan-e7", "Sarah");
+        // A steady 1 kW from before November until after it
+        let readings = vec![
+            ElectricityReading::new(datetime!(2020-10-31 00:00 UTC), 1.0),
+            ElectricityReading::new(datetime!(2020-11-01 00:00 UTC), 1.0),
+            ElectricityReading::new(datetime!(2020-11-30 06:00 UTC), 1.0),
+            ElectricityReading::new(datetime!(2020-11-30 08:00 UTC), 1.0),
+            ElectricityReading::new(datetime!(2020-12-01 00:00 UTC), 1.0),
+        ];
+
+        // 06:00 to 08:00 on the 30th: 1 kWh off-peak and 1 kWh at the standard
+        // rate, with a standing charge for the whole month
+        let bill = Bill::generate(
+            &account,
+            &consumption(&readings[2..4]),
+            &[economy_seven_plan()],
+            period("2020-11").unwrap(),
+            Rounding::PerLine,
+            &VatRates::default(),
+        )
+        .unwrap();
+        let lines = bill
+            .lines
+            .iter()
+            .map(|line| (line.description.as_str(), line.quantity, line.amount))
+            .collect::<Vec<(&str, f64, Money)>>();
+
+        assert_eq!(
+            lines,
+            vec![
+                ("off-peak", 1.0, gbp(0.1)),
+                ("standard", 1.0, gbp(0.3)),
+                ("standing charge", 30.0, gbp(14.4)),
+            ]
+        );
+        assert_eq!(bill.subtotal, gbp(14.8));
+        assert_eq!(bill.taxes[0].amount, gbp(0.74));
+        assert_eq!(bill.total, gbp(15.54));
+
+        let whole_month = Bill::generate(
+            &account,
+            &consumption(&readings),
+            &[economy_seven_plan()],
+            period("2020-11").unwrap(),
+            Rounding::PerLine,
+            &VatRates::default(),
+        )
+        .unwrap();
+
+        assert_eq!(whole_month.consumption_kwh, 720.0);
+        assert_eq!(whole_month.lines[2].quantity, 30.0);
+        assert_eq!(whole_month.lines[2].amount, gbp(14.4));
+    }
+
+    #[test]
+    fn test_bill_splits_at_plan_switch() {
+        let mut account = Account::new("plan-e7", "Sarah");
+        account.switch_price_plan("plan-flat", datetime!(2020-11-30 12:00 UTC));
+        let readings = vec![
+            ElectricityReading::new(datetime!(2020-11-30 10:00 UTC), 1.0),
+            ElectricityReading::new(datetime!(2020-11-30 14:00 UTC), 1.0),
+        ];
+        let flat_plan = PricePlan::new("plan-flat", "Flat", 0.2, HashMap::new());
+
+        let bill = Bill::generate(
+            &account,
+            &consumption(&readings),
+            &[economy_seven_plan(), flat_plan],
+            period("2020-11").unwrap(),
+            Rounding::PerLine,
+            &VatRates::default(),
+        )
+        .unwrap();
+        let lines = bill
+            .lines
+            .iter()
+            .map(|line| (line.price_plan_id.as_str(), line.kind, line.amount))
+            .collect::<Vec<(&str, LineKind, Money)>>();
+
+        assert_eq!(
+            lines,
+            vec![
+                ("plan-e7", LineKind::Consumption, gbp(0.6)),
+                ("plan-e7", LineKind::StandingCharge, gbp(14.16)),
+                ("plan-flat", LineKind::Consumption, gbp(0.4)),
+                ("plan-flat", LineKind::StandingCharge, gbp(0.0)),
+            ]
+        );
+        assert_eq!(bill.taxes.len(), 2);
+        assert_eq!(bill.subtotal, gbp(15.16));
+    }
+
+    #[test]
+    fn test_bill_in_mixed_currencies_is_refused() {
+        let mut account = Account::new("plan-e7", "Sarah");
+        account.switch_price_plan("plan-euro", datetime!(2020-11-30 12:00 UTC));
+        let readings = vec![
+            ElectricityReading::new(datetime!(2020-11-30 10:00 UTC), 1.0),
+            ElectricityReading::new(datetime!(2020-11-30 14:00 UTC), 1.0),
+        ];
+        let mut euro_plan = PricePlan::new("plan-euro", "Euro", 0.2, HashMap::new());
+        euro_plan.currency = Currency::Eur;
+
+        let bill = Bill::generate(
+            &account,
+            &consumption(&readings),
+            &[economy_seven_plan(), euro_plan],
+            period("2020-11").unwrap(),
+            Rounding::PerLine,
+            &VatRates::default(),
+        );
+
+        assert_eq!(bill, None);
+    }
+}
@@ -0,0 +1,924 @@
+use crate::datastore::account::Account;
+use crate::datastore::money::Currency;
+use crate::datastore::plan::{LocalTime, PricePlan, RateBand, RateChange, RateTier, RateVersion};
+use crate::datastore::store::SmartMeterId;
+use crate::datastore::tax::VatCategory;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use time::macros::format_description;
+use time::{OffsetDateTime, Time, UtcOffset, Weekday};
+use time_tz::TimeZone;
+
+/// The accounts and price plans a store starts with
+#[derive(Debug)]
+pub struct Catalogue {
+    pub accounts: HashMap<SmartMeterId, Account>,
+    pub price_plans: Vec<PricePlan>,
+}
+
+#[derive(Debug)]
+pub enum CatalogueError {
+    /// The catalogue file could not be read
+    Io(PathBuf, io::Error),
+    /// The catalogue file is not valid TOML or JSON, or has the wrong shape
+    Parse(PathBuf, String),
+    /// The catalogue parsed but breaks one or more rules
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for CatalogueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(path, e) => write!(f, "cannot read catalogue {}: {e}", path.display()),
+            Self::Parse(path, message) => {
+                write!(f, "cannot parse catalogue {}: {message}", path.display())
+            }
+            Self::Invalid(problems) => write!(f, "invalid catalogue: {}", problems.join("; ")),
+        }
+    }
+}
+
+impl std::error::Error for CatalogueError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CatalogueFile {
+    #[serde(default)]
+    price_plans: Vec<PricePlanDefinition>,
+    #[serde(default)]
+    accounts: Vec<AccountEntry>,
+}
+
+/// A price plan as written in a catalogue file or sent to the price plan API
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PricePlanDefinition {
+    pub id: String,
+    pub name: String,
+    /// The currency of the rates and charges below
+    #[serde(default)]
+    pub currency: Currency,
+    /// Which VAT rate the plan's charges are taxed at
+    #[serde(default)]
+    pub vat_category: VatCategory,
+    pub unit_rate: f64,
+    /// Fixed charge per day
+    #[serde(default)]
+    pub standing_charge: f64,
+    /// Credit per kWh exported to the grid
+    #[serde(default)]
+    pub export_rate: f64,
+    #[serde(default)]
+    pub multipliers: BTreeMap<String, f64>,
+    /// A fixed offset from UTC that rate bands are evaluated in
+    pub utc_offset: Option<String>,
+    /// An IANA time zone that rate bands are evaluated in, following its
+    /// daylight saving rules, instead of a fixed `utc_offset`
+    pub time_zone: Option<String>,
+    #[serde(default)]
+    pub rate_bands: Vec<RateBandDefinition>,
+    #[serde(default)]
+    pub tiers: Vec<RateTierDefinition>,
+    #[serde(default)]
+    pub retired: bool,
+    /// When the rates above took effect, or `None` if they always applied
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub valid_from: Option<OffsetDateTime>,
+    /// Rates the plan charged before the ones above, oldest first
+    #[serde(default)]
+    pub previous_versions: Vec<RateVersionDefinition>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateBandDefinition {
+    pub name: String,
+    pub start: String,
+    pub end: String,
+    pub unit_rate: f64,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateTierDefinition {
+    pub up_to_kwh: f64,
+    pub unit_rate: f64,
+}
+
+/// Rates a price plan charged from `valid_from` until `valid_to`
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateVersionDefinition {
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub valid_from: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub valid_to: OffsetDateTime,
+    pub unit_rate: f64,
+    /// Fixed charge per day
+    #[serde(default)]
+    pub standing_charge: f64,
+    /// Credit per kWh exported to the grid
+    #[serde(default)]
+    pub export_rate: f64,
+    #[serde(default)]
+    pub multipliers: BTreeMap<String, f64>,
+    #[serde(default)]
+    pub rate_bands: Vec<RateBandDefinition>,
+    #[serde(default)]
+    pub tiers: Vec<RateTierDefinition>,
+}
+
+/// New rates for a price plan, replacing its current ones from `valid_from`
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateChangeDefinition {
+    #[serde(with = "time::serde::rfc3339")]
+    pub valid_from: OffsetDateTime,
+    pub unit_rate: f64,
+    /// Fixed charge per day
+    #[serde(default)]
+    pub standing_charge: f64,
+    /// Credit per kWh exported to the grid
+    #[serde(default)]
+    pub export_rate: f64,
+    #[serde(default)]
+    pub multipliers: BTreeMap<String, f64>,
+    #[serde(default)]
+    pub rate_bands: Vec<RateBandDefinition>,
+    #[serde(default)]
+    pub tiers: Vec<RateTierDefinition>,
+}
+
+/// The rate fields shared by plans, their previous versions and rate changes
+struct RateFields<'a> {
+    unit_rate: f64,
+    standing_charge: f64,
+    export_rate: f64,
+    multipliers: &'a BTreeMap<String, f64>,
+    rate_bands: &'a [RateBandDefinition],
+    tiers: &'a [RateTierDefinition],
+}
+
+/// A rule broken by one field of a definition
+#[derive(Clone, Debug, PartialEq)]
+pub struct Problem {
+    pub field: String,
+    pub message: String,
+}
+
+impl Problem {
+    fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.field, self.message)
+    }
+}
+
+impl PricePlanDefinition {
+    /// Checks the definition and builds the price plan it describes
+    ///
+    /// # Returns
+    /// The price plan, or every problem found with the definition
+    pub fn to_price_plan(&self) -> Result<PricePlan, Vec<Problem>> {
+        let mut problems = Vec::new();
+        if self.id.trim().is_empty() {
+            problems.push(Problem::new("id", "must not be empty"));
+        }
+        let (multipliers, rate_bands, tiers) = parse_rates(
+            &mut problems,
+            "",
+            RateFields {
+                unit_rate: self.unit_rate,
+                standing_charge: self.standing_charge,
+                export_rate: self.export_rate,
+                multipliers: &self.multipliers,
+                rate_bands: &self.rate_bands,
+                tiers: &self.tiers,
+            },
+        );
+
+        let mut previous_versions = Vec::new();
+        let mut previous_end = None;
+        for (index, version) in self.previous_versions.iter().enumerate() {
+            let at = format!("previous_versions[{index}]");
+            let (rate_multipliers, rate_bands, tiers) = parse_rates(
+                &mut problems,
+                &format!("{at}."),
+                RateFields {
+                    unit_rate: version.unit_rate,
+                    standing_charge: version.standing_charge,
+                    export_rate: version.export_rate,
+                    multipliers: &version.multipliers,
+                    rate_bands: &version.rate_bands,
+                    tiers: &version.tiers,
+                },
+            );
+            if version
+                .valid_from
+                .is_some_and(|from| from >= version.valid_to)
+            {
+                problems.push(Problem::new(
+                    format!("{at}.valid_to"),
+                    "must be later than valid_from",
+                ));
+            }
+            if previous_end.is_some_and(|end| version.valid_from.is_none_or(|from| from < end)) {
+                problems.push(Problem::new(
+                    format!("{at}.valid_from"),
+                    "must not be before the previous version ends",
+                ));
+            }
+            previous_end = Some(version.valid_to);
+            previous_versions.push(RateVersion {
+                valid_from: version.valid_from,
+                valid_to: version.valid_to,
+                unit_rate: version.unit_rate,
+                standing_charge: version.standing_charge,
+                export_rate: version.export_rate,
+                rate_multipliers,
+                rate_bands,
+                tiers,
+            });
+        }
+        if previous_end.is_some_and(|end| self.valid_from.is_none_or(|from| from < end)) {
+            problems.push(Problem::new(
+                "valid_from",
+                "must not be before the previous version ends",
+            ));
+        }
+
+        let utc_offset = match self.utc_offset.as_deref() {
+            Some(offset) => parse_utc_offset(offset).unwrap_or_else(|| {
+                problems.push(Problem::new("utc_offset", "must look like +01:00"));
+                UtcOffset::UTC
+            }),
+            None => UtcOffset::UTC,
+        };
+        let local_time = match self.time_zone.as_deref() {
+            Some(_) if self.utc_offset.is_some() => {
+                problems.push(Problem::new(
+                    "time_zone",
+                    "must not be given with utc_offset",
+                ));
+                LocalTime::Offset(utc_offset)
+            }
+            Some(name) => LocalTime::zone(name).unwrap_or_else(|| {
+                problems.push(Problem::new(
+                    "time_zone",
+                    "must be an IANA time zone like Europe/London",
+                ));
+                LocalTime::Offset(utc_offset)
+            }),
+            None => LocalTime::Offset(utc_offset),
+        };
+
+        if !problems.is_empty() {
+            return Err(problems);
+        }
+        let mut price_plan = PricePlan::new(&self.id, &self.name, self.unit_rate, multipliers)
+            .with_standing_charge(self.standing_charge)
+            .with_export_rate(self.export_rate)
+            .with_rate_bands(local_time, rate_bands)
+            .with_tiers(tiers);
+        price_plan.currency = self.currency;
+        price_plan.vat_category = self.vat_category;
+        price_plan.retired = self.retired;
+        price_plan.valid_from = self.valid_from;
+        price_plan.previous_versions = previous_versions;
+        Ok(price_plan)
+    }
+}
+
+impl RateChangeDefinition {
+    /// Checks the new rates and gives them to the plan, keeping its current
+    /// rates as the version in force until `valid_from`
+    ///
+    /// # Returns
+    /// Every problem found with the definition, in which case the plan is unchanged
+    pub fn apply_to(&self, price_plan: &mut PricePlan) -> Result<(), Vec<Problem>> {
+        let mut problems = Vec::new();
+        let (multipliers, rate_bands, tiers) = parse_rates(
+            &mut problems,
+            "",
+            RateFields {
+                unit_rate: self.unit_rate,
+                standing_charge: self.standing_charge,
+                export_rate: self.export_rate,
+                multipliers: &self.multipliers,
+                rate_bands: &self.rate_bands,
+                tiers: &self.tiers,
+            },
+        );
+        if price_plan
+            .valid_from
+            .is_some_and(|current| self.valid_from <= current)
+        {
+            problems.push(Problem::new(
+                "valid_from",
+                "must be later than when the current rates took effect",
+            ));
+        }
+
+        if !problems.is_empty() {
+            return Err(problems);
+        }
+        price_plan.change_rates(
+            self.valid_from,
+            RateChange {
+                unit_rate: self.unit_rate,
+                standing_charge: self.standing_charge,
+                export_rate: self.export_rate,
+                rate_multipliers: multipliers,
+                rate_bands,
+                tiers,
+            },
+        );
+        Ok(())
+    }
+}
+
+impl From<&PricePlan> for PricePlanDefinition {
+    fn from(price_plan: &PricePlan) -> Self {
+        let (utc_offset, time_zone) = match price_plan.local_time {
+            LocalTime::Offset(offset) => (Some(format_utc_offset(offset)), None),
+            LocalTime::Zone(zone) => (None, Some(zone.name().to_string())),
+        };
+        Self {
+            id: price_plan.supplier_id.clone(),
+            name: price_plan.plan_name.clone(),
+            currency: price_plan.currency,
+            vat_category: price_plan.vat_category,
+            unit_rate: price_plan.unit_rate,
+            standing_charge: price_plan.standing_charge,
+            export_rate: price_plan.export_rate,
+            multipliers: format_multipliers(&price_plan.rate_multipliers),
+            utc_offset,
+            time_zone,
+            rate_bands: format_rate_bands(&price_plan.rate_bands),
+            tiers: format_tiers(&price_plan.tiers),
+            retired: price_plan.retired,
+            valid_from: price_plan.valid_from,
+            previous_versions: price_plan
+                .previous_versions
+                .iter()
+                .map(|version| RateVersionDefinition {
+                    valid_from: version.valid_from,
+                    valid_to: version.valid_to,
+                    unit_rate: version.unit_rate,
+                    standing_charge: version.standing_charge,
+                    export_rate: version.export_rate,
+                    multipliers: format_multipliers(&version.rate_multipliers),
+                    rate_bands: format_rate_bands(&version.rate_bands),
+                    tiers: format_tiers(&version.tiers),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AccountEntry {
+    smart_meter_id: SmartMeterId,
+    user: String,
+    price_plan_id: String,
+}
+
+impl Catalogue {
+    /// Reads a catalogue file, parsed as JSON when its extension is `.json` and
+    /// as TOML otherwise
+    pub fn from_file(path: &Path) -> Result<Self, CatalogueError> {
+        let contents =
+            std::fs::read_to_string(path).map_err(|e| CatalogueError::Io(path.to_path_buf(), e))?;
+        let file = if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            serde_json::from_str(&contents).map_err(|e| e.to_string())
+        } else {
+            toml::from_str(&contents).map_err(|e| e.to_string())
+        }
+        .map_err(|message| CatalogueError::Parse(path.to_path_buf(), message))?;
+
+        Self::from_entries(file)
+    }
+
+    /// The built-in demo dataset of five accounts and three price plans
+    pub fn demo() -> Self {
+        let accounts = [
+            ("smart-meter-0", "price-plan-0", "Sarah"),
+            ("smart-meter-1", "price-plan-1", "Peter"),
+            ("smart-meter-2", "price-plan-0", "Charlie"),
+            ("smart-meter-3", "price-plan-2", "Andrea"),
+            ("smart-meter-4", "price-plan-1", "Alex"),
+        ]
+        .into_iter()
+        .map(|(smart_meter_id, price_plan_id, user)| {
+            (
+                smart_meter_id.to_string(),
+                Account::new(price_plan_id, user),
+            )
+        })
+        .collect();
+
+        let price_plans = vec![
+            PricePlan::new(
+                "price-plan-0",
+                "Dr Evil's Dark Energy",
+                10.0,
+                HashMap::new(),
+            ),
+            PricePlan::new("price-plan-1", "The Green Eco", 2.0, HashMap::new()),
+            PricePlan::new("price-plan-2", "Power for Everyone", 1.0, HashMap::new()),
+        ];
+
+        Self {
+            accounts,
+            price_plans,
+        }
+    }
+
+    /// Checks the parsed entries and converts them, reporting every problem at once
+    fn from_entries(file: CatalogueFile) -> Result<Self, CatalogueError> {
+        let mut problems = Vec::new();
+
+        let mut plan_ids = HashSet::new();
+        let mut price_plans = Vec::new();
+        for (index, definition) in file.price_plans.iter().enumerate() {
+            let at = format!("price_plans[{index}]");
+            if !plan_ids.insert(definition.id.clone()) {
+                problems.push(format!("{at}.id {} is used by another plan", definition.id));
+            }
+            match definition.to_price_plan() {
+                Ok(price_plan) => price_plans.push(price_plan),
+                Err(plan_problems) => problems.extend(
+                    plan_problems
+                        .into_iter()
+                        .map(|problem| format!("{at}.{problem}")),
+                ),
+            }
+        }
+
+        let mut accounts = HashMap::new();
+        for (index, entry) in file.accounts.into_iter().enumerate() {
+            let at = format!("accounts[{index}]");
+            if entry.smart_meter_id.trim().is_empty() {
+                problems.push(format!("{at}.smart_meter_id must not be empty"));
+            } else if accounts.contains_key(&entry.smart_meter_id) {
+                problems.push(format!(
+                    "{at}.smart_meter_id {} is used by another account",
+                    entry.smart_meter_id
+                ));
+            }
+            if !plan_ids.contains(&entry.price_plan_id) {
+                problems.push(format!(
+                    "{at}.price_plan_id {} does not match any price plan",
+                    entry.price_plan_id
+                ));
+            }
+            accounts.insert(
+                entry.smart_meter_id,
+                Account::new(&entry.price_plan_id, &entry.user),
+            );
+        }
+
+        if !problems.is_empty() {
+            return Err(CatalogueError::Invalid(problems));
+        }
+        Ok(Self {
+            accounts,
+            price_plans,
+        })
+    }
+}
+
+fn check_rate(problems: &mut Vec<Problem>, field: &str, rate: f64) {
+    if !rate.is_finite() || rate < 0.0 {
+        problems.push(Problem::new(field, "must be a non-negative number"));
+    }
+}
+
+/// Checks one version's rates, naming fields after `prefix`
+fn parse_rates(
+    problems: &mut Vec<Problem>,
+    prefix: &str,
+    fields: RateFields<'_>,
+) -> (HashMap<Weekday, f64>, Vec<RateBand>, Vec<RateTier>) {
+    check_rate(problems, &format!("{prefix}unit_rate"), fields.unit_rate);
+    check_rate(
+        problems,
+        &format!("{prefix}standing_charge"),
+        fields.standing_charge,
+    );
+    check_rate(
+        problems,
+        &format!("{prefix}export_rate"),
+        fields.export_rate,
+    );
+
+    let mut parsed_multipliers = HashMap::new();
+    for (day, multiplier) in fields.multipliers {
+        let field = format!("{prefix}multipliers.{day}");
+        check_rate(problems, &field, *multiplier);
+        match parse_weekday(day) {
+            Some(weekday) => {
+                parsed_multipliers.insert(weekday, *multiplier);
+            }
+            None => problems.push(Problem::new(field, "is not a day of the week")),
+        }
+    }
+
+    let mut parsed_bands = Vec::new();
+    for (index, band) in fields.rate_bands.iter().enumerate() {
+        let at = format!("{prefix}rate_bands[{index}]");
+        check_rate(problems, &format!("{at}.unit_rate"), band.unit_rate);
+        let mut time_of_day = |field: &str, value: &str| {
+            parse_time(value).unwrap_or_else(|| {
+                problems.push(Problem::new(
+                    format!("{at}.{field}"),
+                    "must look like 07:30",
+                ));
+                Time::MIDNIGHT
+            })
+        };
+        let start = time_of_day("start", &band.start);
+        let end = time_of_day("end", &band.end);
+        parsed_bands.push(RateBand::new(&band.name, start, end, band.unit_rate));
+    }
+
+    let mut parsed_tiers = Vec::new();
+    let mut previous_limit = 0.0;
+    for (index, tier) in fields.tiers.iter().enumerate() {
+        let at = format!("{prefix}tiers[{index}]");
+        check_rate(problems, &format!("{at}.unit_rate"), tier.unit_rate);
+        if !tier.up_to_kwh.is_finite() || tier.up_to_kwh <= previous_limit {
+            problems.push(Problem::new(
+                format!("{at}.up_to_kwh"),
+                format!("must be greater than {previous_limit}"),
+            ));
+        }
+        previous_limit = tier.up_to_kwh;
+        parsed_tiers.push(RateTier::new(tier.up_to_kwh, tier.unit_rate));
+    }
+
+    (parsed_multipliers, parsed_bands, parsed_tiers)
+}
+
+fn format_multipliers(multipliers: &HashMap<Weekday, f64>) -> BTreeMap<String, f64> {
+    multipliers
+        .iter()
+        .map(|(weekday, multiplier)| (weekday.to_string(), *multiplier))
+        .collect()
+}
+
+fn format_rate_bands(rate_bands: &[RateBand]) -> Vec<RateBandDefinition> {
+    rate_bands
+        .iter()
+        .map(|band| RateBandDefinition {
+            name: band.name.clone(),
+            start: format_time(band.start),
+            end: format_time(band.end),
+            unit_rate: band.unit_rate,
+        })
+        .collect()
+}
+
+fn format_tiers(tiers: &[RateTier]) -> Vec<RateTierDefinition> {
+    tiers
+        .iter()
+        .map(|tier| RateTierDefinition {
+            up_to_kwh: tier.up_to_kwh,
+            unit_rate: tier.unit_rate,
+        })
+        .collect()
+}
+
+fn parse_weekday(day: &str) -> Option<Weekday> {
+    let weekday = match day.to_ascii_lowercase().as_str() {
+        "monday" => Weekday::Monday,
+        "tuesday" => Weekday::Tuesday,
+        "wednesday" => Weekday::Wednesday,
+        "thursday" => Weekday::Thursday,
+        "friday" => Weekday::Friday,
+        "saturday" => Weekday::Saturday,
+        "sunday" => Weekday::Sunday,
+        _ => return None,
+    };
+    Some(weekday)
+}
+
+fn parse_time(value: &str) -> Option<Time> {
+    Time::parse(value, format_description!("[hour]:[minute]")).ok()
+}
+
+fn format_time(time: Time) -> String {
+    format!("{:02}:{:02}", time.hour(), time.minute())
+}
+
+fn parse_utc_offset(value: &str) -> Option<UtcOffset> {
+    UtcOffset::parse(
+        value,
+        format_description!("[offset_hour sign:mandatory]:[offset_minute]"),
+    )
+    .ok()
+}
+
+fn format_utc_offset(offset: UtcOffset) -> String {
+    let (hours, minutes, _) = offset.as_hms();
+    let sign = if offset.is_negative() { '-' } else { '+' };
+    format!("{sign}{:02}:{:02}", hours.abs(), minutes.abs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::{datetime, offset, time};
+
+    fn parse(contents: &str) -> Result<Catalogue, CatalogueError> {
+        Catalogue::from_entries(toml::from_str(contents).unwrap())
+    }
+
+    #[test]
+    fn test_shipped_demo_catalogue_matches_builtin_demo() {
+        let shipped = Catalogue::from_file(Path::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/config/demo.toml"
+        )))
+        .unwrap();
+        let demo = Catalogue::demo();
+
+        assert_eq!(shipped.accounts.len(), demo.accounts.len());
+        for (smart_meter_id, account) in &demo.accounts {
+            assert_eq!(&shipped.accounts[smart_meter_id], account);
+        }
+        let rates = |catalogue: &Catalogue| {
+            catalogue
+                .price_plans
+                .iter()
+                .map(|plan| (plan.supplier_id.clone(), plan.unit_rate))
+                .collect::<Vec<(String, f64)>>()
+        };
+        assert_eq!(rates(&shipped), rates(&demo));
+    }
+
+    #[test]
+    fn test_plan_with_multipliers_and_bands() {
+        let catalogue = parse(
+            r#"
+            [[price_plans]]
+            id = "night-saver"
+            name = "Night Saver"
+            unit_rate = 3.0
+            standing_charge = 50.0
+            utc_offset = "+01:00"
+            multipliers = { Saturday = 0.5 }
+            rate_bands = [{ name = "night", start = "23:00", end = "07:00", unit_rate = 1.0 }]
+
+            [[accounts]]
+            smart_meter_id = "meter-1"
+            user = "Sam"
+            price_plan_id = "night-saver"
+            "#,
+        )
+        .unwrap();
+
+        let plan = &catalogue.price_plans[0];
+        assert_eq!(plan.unit_rate_on(Weekday::Saturday), 1.5);
+        assert_eq!(plan.local_time, LocalTime::Offset(offset!(+1)));
+        assert_eq!(plan.standing_charge, 50.0);
+        assert_eq!(
+            plan.rate_bands,
+            vec![RateBand::new("night", time!(23:00), time!(07:00), 1.0)]
+        );
+        assert_eq!(
+            catalogue.accounts["meter-1"].price_plan_at(OffsetDateTime::now_utc()),
+            "night-saver"
+        );
+    }
+
+    #[test]
+    fn test_plan_in_time_zone() {
+        let catalogue = parse(
+            r#"
+            [[price_plans]]
+            id = "economy-7"
+            name = "Economy 7"
+            unit_rate = 3.0
+            time_zone = "Europe/London"
+            rate_bands = [{ name = "night", start = "00:00", end = "07:00", unit_rate = 1.0 }]
+            "#,
+        )
+        .unwrap();
+
+        let plan = &catalogue.price_plans[0];
+        assert_eq!(plan.local_time, LocalTime::zone("Europe/London").unwrap());
+        let definition = PricePlanDefinition::from(plan);
+        assert_eq!(definition.time_zone.as_deref(), Some("Europe/London"));
+        assert_eq!(definition.utc_offset, None);
+
+        let problems = |time_zone: &str, utc_offset: Option<&str>| {
+            PricePlanDefinition {
+                time_zone: Some(time_zone.to_string()),
+                utc_offset: utc_offset.map(str::to_string),
+                ..definition.clone()
+            }
+            .to_price_plan()
+            .unwrap_err()
+        };
+        assert_eq!(
+            problems("Europe/Atlantis", None),
+            vec![Problem::new(
+                "time_zone",
+                "must be an IANA time zone like Europe/London"
+            )]
+        );
+        assert_eq!(
+            problems("Europe/London", Some("+01:00")),
+            vec![Problem::new(
+                "time_zone",
+                "must not be given with utc_offset"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_plan_with_previous_versions() {
+        let catalogue = parse(
+            r#"
+            [[price_plans]]
+            id = "flat"
+            name = "Flat"
+            unit_rate = 3.0
+            valid_from = "2024-04-01T00:00:00Z"
+
+            [[price_plans.previous_versions]]
+            valid_to = "2024-01-01T00:00:00Z"
+            unit_rate = 1.0
+
+            [[price_plans.previous_versions]]
+            valid_from = "2024-01-01T00:00:00Z"
+            valid_to = "2024-04-01T00:00:00Z"
+            unit_rate = 2.0
+            "#,
+        )
+        .unwrap();
+
+        let plan = &catalogue.price_plans[0];
+        assert_eq!(plan.unit_rate_at(datetime!(2023-06-01 00:00:00 UTC)), 1.0);
+        assert_eq!(plan.unit_rate_at(datetime!(2024-02-01 00:00:00 UTC)), 2.0);
+        assert_eq!(plan.unit_rate_at(datetime!(2024-06-01 00:00:00 UTC)), 3.0);
+        let definition = PricePlanDefinition::from(plan);
+        assert_eq!(
+            PricePlanDefinition::from(&definition.to_price_plan().unwrap()),
+            definition
+        );
+    }
+
+    #[test]
+    fn test_overlapping_versions_are_rejected() {
+        let definition = PricePlanDefinition {
+            valid_from: Some(datetime!(2024-01-01 00:00:00 UTC)),
+            previous_versions: vec![RateVersionDefinition {
+                valid_from: None,
+                valid_to: datetime!(2024-02-01 00:00:00 UTC),
+                unit_rate: 1.0,
+                standing_charge: 0.0,
+                export_rate: 0.0,
+                multipliers: BTreeMap::new(),
+                rate_bands: Vec::new(),
+                tiers: Vec::new(),
+            }],
+            ..PricePlanDefinition::from(&PricePlan::new("flat", "Flat", 2.0, HashMap::new()))
+        };
+
+        assert_eq!(
+            definition.to_price_plan().unwrap_err(),
+            vec![Problem::new(
+                "valid_from",
+                "must not be before the previous version ends"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_tiered_plan() {
+        let catalogue = parse(
+            r#"
+            [[price_plans]]
+            id = "co-op-tiered"
+            name = "Co-op Tiered"
+            unit_rate = 3.0
+            tiers = [{ up_to_kwh = 50.0, unit_rate = 1.0 }, { up_to_kwh = 100.0, unit_rate = 2.0 }]
+            "#,
+        )
+        .unwrap();
+
+        let plan = &catalogue.price_plans[0];
+        assert_eq!(
+            plan.tiers,
+            vec![RateTier::new(50.0, 1.0), RateTier::new(100.0, 2.0)]
+        );
+        let definition = PricePlanDefinition::from(plan);
+        assert_eq!(
+            PricePlanDefinition::from(&definition.to_price_plan().unwrap()),
+            definition
+        );
+    }
+
+    #[test]
+    fn test_tiers_out_of_order_are_rejected() {
+        let catalogue = parse(
+            r#"
+            [[price_plans]]
+            id = "co-op-tiered"
+            name = "Co-op Tiered"
+            unit_rate = 3.0
+            tiers = [{ up_to_kwh = 100.0, unit_rate = 1.0 }, { up_to_kwh = 50.0, unit_rate = 2.0 }]
+            "#,
+        );
+
+        let Err(CatalogueError::Invalid(problems)) = catalogue else {
+            panic!("expected the catalogue to be invalid");
+        };
+        assert_eq!(
+            problems,
+            vec!["price_plans[0].tiers[1].up_to_kwh must be greater than 100"]
+        );
+    }
+
+    #[test]
+    fn test_json_catalogue() {
+        let path = std::env::temp_dir().join(format!("joi-catalogue-{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{"price_plans": [{"id": "plan-1", "name": "Plan", "unit_rate": 1.0}], "accounts": []}"#,
+        )
+        .unwrap();
+
+        let catalogue = Catalogue::from_file(&path).unwrap();
+
+        assert_eq!(catalogue.price_plans.len(), 1);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_all_problems_are_reported() {
+        let result = parse(
+            r#"
+            [[price_plans]]
+            id = "plan-1"
+            name = "Plan"
+            unit_rate = -1.0
+            multipliers = { Funday = 2.0 }
+
+            [[price_plans]]
+            id = "plan-1"
+            name = "Same Plan"
+            unit_rate = 1.0
+
+            [[accounts]]
+            smart_meter_id = "meter-1"
+            user = "Sam"
+            price_plan_id = "plan-2"
+            "#,
+        );
+
+        let Err(CatalogueError::Invalid(problems)) = result else {
+            panic!("expected the catalogue to be invalid");
+        };
+        assert_eq!(
+            problems,
+            vec![
+                "price_plans[0].unit_rate must be a non-negative number",
+                "price_plans[0].multipliers.Funday is not a day of the week",
+                "price_plans[1].id plan-1 is used by another plan",
+                "accounts[0].price_plan_id plan-2 does not match any price plan",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_fields_are_rejected() {
+        assert!(toml::from_str::<CatalogueFile>(
+            "[[price_plans]]\nid = \"plan\"\ncolour = \"red\"\n"
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,270 @@
+use crate::datastore::reading::ElectricityReading;
+use serde::Deserialize;
+use time::OffsetDateTime;
+
+/// How power is assumed to vary between two consecutive kW samples
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegrationMethod {
+    /// Power changes linearly from one sample to the next
+    #[default]
+    Trapezoidal,
+    /// Each sample is held until the next one is taken
+    StepHold,
+}
+
+/// The span between two consecutive readings, with power varying linearly
+/// from `start_kw` to `end_kw` across it
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConsumptionInterval {
+    pub start: OffsetDateTime,
+    pub end: OffsetDateTime,
+    pub start_kw: f64,
+    pub end_kw: f64,
+}
+
+impl ConsumptionInterval {
+    pub fn hours(&self) -> f64 {
+        (self.end - self.start).as_seconds_f64() / 3600.0
+    }
+
+    pub fn energy_kwh(&self) -> f64 {
+        self.energy_between(self.start, self.end)
+    }
+
+    /// Calculates the energy used in the part of the interval between `from` and `to`
+    ///
+    /// Bounds outside the interval are clamped to it.
+    pub fn energy_between(&self, from: OffsetDateTime, to: OffsetDateTime) -> f64 {
+        let from = from.max(self.start);
+        let to = to.min(self.end);
+        if from >= to {
+            return 0.0;
+        }
+        let hours = (to - from).as_seconds_f64() / 3600.0;
+        (self.power_at(from) + self.power_at(to)) / 2.0 * hours
+    }
+
+    /// Returns the part of the interval between `from` and `to`, if they overlap
+    pub fn clip(&self, from: OffsetDateTime, to: OffsetDateTime) -> Option<Self> {
+        let start = from.max(self.start);
+        let end = to.min(self.end);
+        if start >= end {
+            return None;
+        }
+        Some(Self {
+            start,
+            end,
+            start_kw: self.power_at(start),
+            end_kw: self.power_at(end),
+        })
+    }
+
+    fn power_at(&self, instant: OffsetDateTime) -> f64 {
+        let total = (self.end - self.start).as_seconds_f64();
+        if total == 0.0 {
+            return self.start_kw;
+        }
+        let elapsed = (instant - self.start).as_seconds_f64();
+        self.start_kw + (self.end_kw - self.start_kw) * elapsed / total
+    }
+}
+
+/// Energy consumption of a meter, integrated from its kW readings
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Consumption {
+    pub intervals: Vec<ConsumptionInterval>,
+    /// Energy exported to the grid, integrated the same way from the readings'
+    /// export values, or empty when no reading reports an export
+    pub exports: Vec<ConsumptionInterval>,
+}
+
+impl Consumption {
+    /// Integrates a collection of kW readings into kWh intervals
+    ///
+    /// A reading without an export value counts as exporting nothing when
+    /// others in the collection have one.
+    ///
+    /// # Arguments
+    /// * `stored_readings` - Electricity readings in any order
+    /// * `method` - How power is assumed to vary between readings
+    pub fn from_readings(
+        stored_readings: &[ElectricityReading],
+        method: IntegrationMethod,
+    ) -> Self {
+        let mut sorted_readings = stored_readings.to_vec();
+        sorted_readings.sort_by_key(|r| r.time);
+
+        let intervals = integrate(&sorted_readings, method, |r| r.reading);
+        let exports = if sorted_readings.iter().any(|r| r.export.is_some()) {
+            integrate(&sorted_readings, method, |r| r.export.unwrap_or_default())
+        } else {
+            Vec::new()
+        };
+
+        Self { intervals, exports }
+    }
+
+    pub fn total_kwh(&self) -> f64 {
+        self.intervals
+            .iter()
+            .map(ConsumptionInterval::energy_kwh)
+            .sum()
+    }
+
+    pub fn total_export_kwh(&self) -> f64 {
+        self.exports
+            .iter()
+            .map(ConsumptionInterval::energy_kwh)
+            // Folded from 0.0, as summing no floats gives -0.0
+            .fold(0.0, |total, kwh| total + kwh)
+    }
+
+    pub fn total_hours(&self) -> f64 {
+        self.intervals.iter().map(ConsumptionInterval::hours).sum()
+    }
+
+    /// Returns the consumption between `from` and `to`, splitting intervals
+    /// that cross either bound
+    ///
+    /// A missing bound leaves that end of the range open.
+    pub fn between(&self, from: Option<OffsetDateTime>, to: Option<OffsetDateTime>) -> Self {
+        let clip = |intervals: &[ConsumptionInterval]| {
+            intervals
+                .iter()
+                .filter_map(|interval| {
+                    interval.clip(from.unwrap_or(interval.start), to.unwrap_or(interval.end))
+                })
+                .collect()
+        };
+        Self {
+            intervals: clip(&self.intervals),
+            exports: clip(&self.exports),
+        }
+    }
+}
+
+/// Builds an interval between each pair of consecutive sorted readings from
+/// the power `kw` picks out of them
+fn integrate(
+    sorted_readings: &[ElectricityReading],
+    method: IntegrationMethod,
+    kw: impl Fn(&ElectricityReading) -> f64,
+) -> Vec<ConsumptionInterval> {
+    sorted_readings
+        .windows(2)
+        .map(|pair| ConsumptionInterval {
+            start: pair[0].time,
+            end: pair[1].time,
+            start_kw: kw(&pair[0]),
+            end_kw: match method {
+                IntegrationMethod::Trapezoidal => kw(&pair[1]),
+                IntegrationMethod::StepHold => kw(&pair[0]),
+            },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    fn readings() -> Vec<ElectricityReading> {
+        vec![
+            ElectricityReading::new(datetime!(2020-11-29 08:00:00 UTC), 1.0),
+            ElectricityReading::new(datetime!(2020-11-29 09:00:00 UTC), 3.0),
+            ElectricityReading::new(datetime!(2020-11-29 11:00:00 UTC), 1.0),
+        ]
+    }
+
+    #[test]
+    fn test_trapezoidal_integration() {
+        let consumption = Consumption::from_readings(&readings(), IntegrationMethod::Trapezoidal);
+
+        assert_eq!(consumption.intervals.len(), 2);
+        assert_eq!(consumption.total_kwh(), 6.0);
+        assert_eq!(consumption.total_hours(), 3.0);
+    }
+
+    #[test]
+    fn test_step_hold_integration() {
+        let consumption = Consumption::from_readings(&readings(), IntegrationMethod::StepHold);
+
+        assert_eq!(consumption.total_kwh(), 7.0);
+    }
+
+    #[test]
+    fn test_unsorted_readings_are_integrated_chronologically() {
+        let mut unsorted = readings();
+        unsorted.reverse();
+        let consumption = Consumption::from_readings(&unsorted, IntegrationMethod::Trapezoidal);
+
+        assert_eq!(consumption.total_kwh(), 6.0);
+    }
+
+    #[test]
+    fn test_single_reading_has_no_consumption() {
+        let consumption =
+            Consumption::from_readings(&readings()[..1], IntegrationMethod::Trapezoidal);
+
+        assert_eq!(consumption.total_kwh(), 0.0);
+        assert_eq!(consumption.total_hours(), 0.0);
+    }
+
+    #[test]
+    fn test_between_splits_intervals() {
+        let consumption = Consumption::from_readings(&readings(), IntegrationMethod::Trapezoidal);
+
+        let before = consumption.between(None, Some(datetime!(2020-11-29 08:30:00 UTC)));
+        let after = consumption.between(Some(datetime!(2020-11-29 08:30:00 UTC)), None);
+
+        assert_eq!(before.total_kwh(), 0.75);
+        assert_eq!(after.total_kwh(), 5.25);
+        assert_eq!(after.intervals[0].start_kw, 2.0);
+    }
+
+    #[test]
+    fn test_energy_between_interpolates_power() {
+        let interval = ConsumptionInterval {
+            start: datetime!(2020-11-29 08:00:00 UTC),
+            end: datetime!(2020-11-29 10:00:00 UTC),
+            start_kw: 0.0,
+            end_kw: 4.0,
+        };
+
+        let first_hour = interval.energy_between(
+            datetime!(2020-11-29 07:00:00 UTC),
+            datetime!(2020-11-29 09:00:00 UTC),
+        );
+
+        assert_eq!(first_hour, 1.0);
+        assert_eq!(interval.energy_kwh(), 4.0);
+    }
+
+    #[test]
+    fn test_exports_are_integrated_separately() {
+        let solar_readings = vec![
+            ElectricityReading::new(datetime!(2020-11-29 08:00:00 UTC), 1.0).with_export(2.0),
+            ElectricityReading::new(datetime!(2020-11-29 09:00:00 UTC), 1.0),
+            ElectricityReading::new(datetime!(2020-11-29 10:00:00 UTC), 1.0).with_export(4.0),
+        ];
+
+        let consumption =
+            Consumption::from_readings(&solar_readings, IntegrationMethod::Trapezoidal);
+
+        assert_eq!(consumption.total_kwh(), 2.0);
+        assert_eq!(consumption.total_export_kwh(), 3.0);
+        assert_eq!(
+            consumption
+                .between(None, Some(datetime!(2020-11-29 09:00:00 UTC)))
+                .total_export_kwh(),
+            1.0
+        );
+        assert!(
+            Consumption::from_readings(&readings(), IntegrationMethod::StepHold)
+                .exports
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,64 @@
+use crate::datastore::store::SmartMeterId;
+use std::fmt;
+use std::io;
+use time::OffsetDateTime;
+
+#[derive(Debug)]
+pub enum DataStoreError {
+    /// No account is registered for the smart meter
+    UnknownSmartMeter(SmartMeterId),
+    /// An account is already registered for the smart meter
+    DuplicateSmartMeter(SmartMeterId),
+    /// No price plan has the given id
+    UnknownPricePlan(String),
+    /// A price plan with the given id already exists
+    DuplicatePricePlan(String),
+    /// Readings clash with stored readings taken at the same times
+    ConflictingReadings(Vec<OffsetDateTime>),
+    /// The store could not be read from or written to
+    Io(io::Error),
+}
+
+impl fmt::Display for DataStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownSmartMeter(smart_meter_id) => {
+                write!(
+                    f,
+                    "no account is registered for smart meter {smart_meter_id}"
+                )
+            }
+            Self::DuplicateSmartMeter(smart_meter_id) => write!(
+                f,
+                "an account is already registered for smart meter {smart_meter_id}"
+            ),
+            Self::UnknownPricePlan(price_plan_id) => {
+                write!(f, "no price plan has the id {price_plan_id}")
+            }
+            Self::DuplicatePricePlan(price_plan_id) => {
+                write!(f, "a price plan with the id {price_plan_id} already exists")
+            }
+            Self::ConflictingReadings(times) => write!(
+                f,
+                "{} readings conflict with different readings stored for the same time",
+                times.len()
+            ),
+            Self::Io(e) => write!(f, "storage error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for DataStoreError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DataStoreError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
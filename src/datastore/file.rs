@@ -0,0 +1,442 @@
+use crate::datastore::account::Account;
+use crate::datastore::catalogue::PricePlanDefinition;
+use crate::datastore::error::DataStoreError;
+use crate::datastore::plan::{HalfHourPrice, PricePlan};
+use crate::datastore::reading::ElectricityReading;
+use crate::datastore::store::{
+    ConflictPolicy, DataStore, InMemoryDataStore, InsertSummary, ReadingsPage, SmartMeterId,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use time::OffsetDateTime;
+
+/// A single change to the store, written as one JSON line in the log
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum LogEntry {
+    /// Readings to store, each replacing any stored reading at its timestamp
+    Readings {
+        smart_meter_id: SmartMeterId,
+        readings: Vec<ElectricityReading>,
+    },
+    /// A price plan to store, replacing any stored plan with the same id
+    PricePlan { price_plan: PricePlanDefinition },
+    /// Half-hourly prices for a plan, each replacing any price for its half hour
+    HalfHourlyPrices {
+        price_plan_id: String,
+        prices: Vec<HalfHourPrice>,
+    },
+    /// An account to register for a meter, replacing any account it has
+    Account {
+        smart_meter_id: SmartMeterId,
+        account: Account,
+    },
+    /// The meter's account was removed
+    AccountDeregistered { smart_meter_id: SmartMeterId },
+}
+
+/// A `DataStore` that persists changes to an append-only log file
+///
+/// Every change is appended to the log before it is applied in memory, and the
+/// log is replayed when the store is opened so data survives restarts.
+#[derive(Debug)]
+pub struct FileDataStore {
+    inner: InMemoryDataStore,
+    log: File,
+}
+
+impl FileDataStore {
+    /// Opens the log at `path`, creating it if needed, and replays its entries
+    ///
+    /// Every entry is written as a whole line, so a last line without a newline
+    /// was cut short by a crash while it was being appended. It is discarded,
+    /// along with the change it would have made, and the log is truncated to
+    /// the last complete entry. Any other entry that cannot be read fails the
+    /// open.
+    ///
+    /// # Arguments
+    /// * `path` - Location of the append-only log file
+    /// * `accounts` - Accounts known to the store
+    /// * `price_plans` - Price plans available to accounts
+    pub fn open(
+        path: impl AsRef<Path>,
+        accounts: HashMap<SmartMeterId, Account>,
+        price_plans: Vec<PricePlan>,
+    ) -> Result<Self, DataStoreError> {
+        let path = path.as_ref();
+        let mut log = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+        let mut contents = Vec::new();
+        log.read_to_end(&mut contents)?;
+        let complete = contents
+            .iter()
+            .rposition(|byte| *byte == b'\n')
+            .map_or(0, |newline| newline + 1);
+
+        let mut inner = InMemoryDataStore::new(accounts, HashMap::new(), price_plans);
+        for line in contents[..complete].split(|byte| *byte == b'\n') {
+            if line.trim_ascii().is_empty() {
+                continue;
+            }
+            Self::apply(
+                &mut inner,
+                serde_json::from_slice(line).map_err(io::Error::from)?,
+            )?;
+        }
+        if !contents[complete..].trim_ascii().is_empty() {
+            tracing::warn!("discarding the incomplete last entry of {}", path.display());
+        }
+        if complete < contents.len() {
+            log.set_len(complete as u64)?;
+        }
+
+        Ok(Self { inner, log })
+    }
+
+    /// Writes an entry to the log and then applies it to the in-memory state
+    fn commit(&mut self, entry: LogEntry) -> Result<(), DataStoreError> {
+        let mut line = serde_json::to_vec(&entry).map_err(io::Error::from)?;
+        line.push(b'\n');
+        self.log.write_all(&line)?;
+        self.log.sync_data()?;
+        Self::apply(&mut self.inner, entry)
+    }
+
+    fn apply(inner: &mut InMemoryDataStore, entry: LogEntry) -> Result<(), DataStoreError> {
+        match entry {
+            LogEntry::Readings {
+                smart_meter_id,
+                readings,
+            } => {
+                inner.index_readings(smart_meter_id, readings);
+                Ok(())
+            }
+            LogEntry::PricePlan { price_plan } => {
+                let price_plan = price_plan.to_price_plan().map_err(|problems| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("invalid price plan {}: {}", price_plan.id, problems[0]),
+                    )
+                })?;
+                inner.save_price_plan(price_plan);
+                Ok(())
+            }
+            LogEntry::HalfHourlyPrices {
+                price_plan_id,
+                prices,
+            } => inner.save_half_hourly_prices(&price_plan_id, prices),
+            LogEntry::Account {
+                smart_meter_id,
+                account,
+            } => {
+                inner.save_account(smart_meter_id, account);
+                Ok(())
+            }
+            LogEntry::AccountDeregistered { smart_meter_id } => {
+                inner.remove_account(&smart_meter_id);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl DataStore for FileDataStore {
+    fn insert_readings_with_policy(
+        &mut self,
+        smart_meter_id: SmartMeterId,
+        readings: Vec<ElectricityReading>,
+        policy: ConflictPolicy,
+    ) -> Result<InsertSummary, DataStoreError> {
+        let (writes, summary) = self
+            .inner
+            .resolve_conflicts(&smart_meter_id, &readings, policy)?;
+        if !writes.is_empty() {
+            self.commit(LogEntry::Readings {
+                smart_meter_id,
+                readings: writes,
+            })?;
+        }
+        Ok(summary)
+    }
+
+    fn get_readings_between(
+        &self,
+        smart_meter_id: &SmartMeterId,
+        from: Option<OffsetDateTime>,
+        to: Option<OffsetDateTime>,
+    ) -> Vec<ElectricityReading> {
+        self.inner.get_readings_between(smart_meter_id, from, to)
+    }
+
+    fn get_readings_page(
+        &self,
+        smart_meter_id: &SmartMeterId,
+        from: Option<OffsetDateTime>,
+        to: Option<OffsetDateTime>,
+        after: Option<OffsetDateTime>,
+        limit: usize,
+    ) -> ReadingsPage {
+        self.inner
+            .get_readings_page(smart_meter_id, from, to, after, limit)
+    }
+
+    fn get_price_plans(&self) -> Vec<PricePlan> {
+        self.inner.get_price_plans()
+    }
+
+    fn add_price_plan(&mut self, price_plan: PricePlan) -> Result<(), DataStoreError> {
+        if self.inner.get_price_plan(&price_plan.supplier_id).is_some() {
+            return Err(DataStoreError::DuplicatePricePlan(price_plan.supplier_id));
+        }
+        self.commit(LogEntry::PricePlan {
+            price_plan: PricePlanDefinition::from(&price_plan),
+        })
+    }
+
+    fn update_price_plan(&mut self, price_plan: PricePlan) -> Result<(), DataStoreError> {
+        if self.inner.get_price_plan(&price_plan.supplier_id).is_none() {
+            return Err(DataStoreError::UnknownPricePlan(price_plan.supplier_id));
+        }
+        self.commit(LogEntry::PricePlan {
+            price_plan: PricePlanDefinition::from(&price_plan),
+        })
+    }
+
+    fn add_half_hourly_prices(
+        &mut self,
+        price_plan_id: &str,
+        prices: Vec<HalfHourPrice>,
+    ) -> Result<(), DataStoreError> {
+        if self.inner.get_price_plan(price_plan_id).is_none() {
+            return Err(DataStoreError::UnknownPricePlan(price_plan_id.to_string()));
+        }
+        self.commit(LogEntry::HalfHourlyPrices {
+            price_plan_id: price_plan_id.to_string(),
+            prices,
+        })
+    }
+
+    fn get_accounts(&self) -> BTreeMap<SmartMeterId, Account> {
+        self.inner.get_accounts()
+    }
+
+    fn get_account(&self, smart_meter_id: &SmartMeterId) -> Option<Account> {
+        self.inner.get_account(smart_meter_id)
+    }
+
+    fn register_account(
+        &mut self,
+        smart_meter_id: SmartMeterId,
+        account: Account,
+    ) -> Result<(), DataStoreError> {
+        if self.inner.get_account(&smart_meter_id).is_some() {
+            return Err(DataStoreError::DuplicateSmartMeter(smart_meter_id));
+        }
+        self.commit(LogEntry::Account {
+            smart_meter_id,
+            account,
+        })
+    }
+
+    fn update_account(
+        &mut self,
+        smart_meter_id: SmartMeterId,
+        account: Account,
+    ) -> Result<(), DataStoreError> {
+        if self.inner.get_account(&smart_meter_id).is_none() {
+            return Err(DataStoreError::UnknownSmartMeter(smart_meter_id));
+        }
+        self.commit(LogEntry::Account {
+            smart_meter_id,
+            account,
+        })
+    }
+
+    fn deregister_account(&mut self, smart_meter_id: &SmartMeterId) -> Result<(), DataStoreError> {
+        if self.inner.get_account(smart_meter_id).is_none() {
+            return Err(DataStoreError::UnknownSmartMeter(
+                smart_meter_id.to_string(),
+            ));
+        }
+        self.commit(LogEntry::AccountDeregistered {
+            smart_meter_id: smart_meter_id.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use time::macros::datetime;
+
+    fn log_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("joi-{}-{}.log", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn open_store(path: &Path) -> FileDataStore {
+        let accounts = HashMap::from([("meter-1".to_string(), Account::new("plan-1", "user-1"))]);
+        let price_plans = vec![PricePlan::new("plan-1", "plan-1", 10.0, HashMap::new())];
+        FileDataStore::open(path, accounts, price_plans).unwrap()
+    }
+
+    #[test]
+    fn test_readings_survive_reopening() {
+        let path = log_path("reopen");
+        let readings = vec![
+            ElectricityReading::new(datetime!(2020-11-29 08:00:00 UTC), 1.5),
+            ElectricityReading::new(datetime!(2020-11-29 08:01:00 UTC), 2.5),
+        ];
+        {
+            let mut store = open_store(&path);
+            store
+                .insert_readings("meter-1".to_string(), readings.clone())
+                .unwrap();
+        }
+
+        let store = open_store(&path);
+
+        assert_eq!(store.get_readings(&"meter-1".to_string()), readings);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_price_plan_changes_survive_reopening() {
+        let path = log_path("plans");
+        {
+            let mut store = open_store(&path);
+            store
+                .add_price_plan(PricePlan::new("plan-2", "plan-2", 5.0, HashMap::new()))
+                .unwrap();
+            let mut retired = store.get_price_plan("plan-1").unwrap();
+            retired.retired = true;
+            store.update_price_plan(retired).unwrap();
+        }
+
+        let store = open_store(&path);
+
+        assert!(store.get_price_plan("plan-1").unwrap().retired);
+        assert_eq!(store.get_price_plan("plan-2").unwrap().unit_rate, 5.0);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_half_hourly_prices_survive_reopening_and_plan_updates() {
+        let path = log_path("prices");
+        {
+            let mut store = open_store(&path);
+            store
+                .add_half_hourly_prices(
+                    "plan-1",
+                    vec![HalfHourPrice::new(datetime!(2020-11-29 08:00:00 UTC), 4.0)],
+                )
+                .unwrap();
+            let mut retired = store.get_price_plan("plan-1").unwrap();
+            retired.retired = true;
+            retired.half_hourly_prices.clear();
+            store.update_price_plan(retired).unwrap();
+        }
+
+        let store = open_store(&path);
+
+        assert_eq!(
+            store
+                .get_price_plan("plan-1")
+                .unwrap()
+                .half_hourly_price_at(datetime!(2020-11-29 08:15:00 UTC)),
+            Some(4.0)
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_account_changes_survive_reopening() {
+        let path = log_path("accounts");
+        {
+            let mut store = open_store(&path);
+            store
+                .register_account("meter-2".to_string(), Account::new("plan-1", "user-2"))
+                .unwrap();
+            store.deregister_account(&"meter-1".to_string()).unwrap();
+        }
+
+        let store = open_store(&path);
+
+        assert_eq!(
+            store.get_account(&"meter-2".to_string()),
+            Some(Account::new("plan-1", "user-2"))
+        );
+        assert_eq!(store.get_account(&"meter-1".to_string()), None);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_log_fails_to_open() {
+        let path = log_path("corrupt");
+        std::fs::write(&path, "not json\n").unwrap();
+
+        let accounts = HashMap::new();
+        assert!(FileDataStore::open(&path, accounts, Vec::new()).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_incomplete_last_entry_is_discarded() {
+        let path = log_path("incomplete");
+        let reading = ElectricityReading::new(datetime!(2020-11-29 08:00:00 UTC), 1.5);
+        {
+            let mut store = open_store(&path);
+            store
+                .insert_readings("meter-1".to_string(), vec![reading.clone()])
+                .unwrap();
+        }
+        let mut log = OpenOptions::new().append(true).open(&path).unwrap();
+        log.write_all(br#"{"type":"readings","smart_meter_id":"me"#)
+            .unwrap();
+
+        {
+            let mut store = open_store(&path);
+            assert_eq!(
+                store.get_readings(&"meter-1".to_string()),
+                vec![reading.clone()]
+            );
+            store
+                .register_account("meter-2".to_string(), Account::new("plan-1", "user-2"))
+                .unwrap();
+        }
+
+        let store = open_store(&path);
+
+        assert_eq!(store.get_readings(&"meter-1".to_string()), vec![reading]);
+        assert!(store.get_account(&"meter-2".to_string()).is_some());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_accounts_logged_before_plan_history_are_replayed() {
+        let path = log_path("legacy-account");
+        drop(open_store(&path));
+        let mut log = OpenOptions::new().append(true).open(&path).unwrap();
+        log.write_all(
+            b"{\"type\":\"account\",\"smart_meter_id\":\"meter-2\",\
+              \"account\":{\"price_plan_id\":\"plan-1\",\"user\":\"user-2\"}}\n",
+        )
+        .unwrap();
+
+        let store = open_store(&path);
+
+        assert_eq!(
+            store.get_account(&"meter-2".to_string()),
+            Some(Account::new("plan-1", "user-2"))
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+}
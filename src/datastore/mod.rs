@@ -1,5 +1,12 @@
 pub mod account;
+pub mod bill;
+pub mod catalogue;
+pub mod consumption;
+pub mod error;
+pub mod file;
+pub mod money;
 pub mod plan;
 pub mod reading;
 pub mod state;
 pub mod store;
+pub mod tax;
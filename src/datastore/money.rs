@@ -0,0 +1,331 @@
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, AddAssign, Mul, Neg, Sub};
+
+/// Digits kept after the decimal point of every amount, well beyond any
+/// currency's minor unit so that only explicit rounding ever loses precision
+const FRACTION_DIGITS: u32 = 9;
+const SCALE: i128 = 10i128.pow(FRACTION_DIGITS);
+
+/// The currency an amount of money is in, written as its ISO 4217 code
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Currency {
+    #[default]
+    Gbp,
+    Eur,
+    Usd,
+}
+
+impl Currency {
+    /// The number of digits after the decimal point in the currency's minor unit
+    pub fn minor_digits(self) -> u32 {
+        match self {
+            Self::Gbp | Self::Eur | Self::Usd => 2,
+        }
+    }
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Gbp => "GBP",
+            Self::Eur => "EUR",
+            Self::Usd => "USD",
+        }
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+/// An amount of money in a currency, held as an exact decimal
+///
+/// Amounts keep nine decimal places, so sums of many small costs do not drift.
+/// They are only rounded to the currency's minor unit by [`Money::round`].
+/// Adding or subtracting amounts in different currencies panics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Money {
+    /// The amount in billionths of the currency's major unit
+    amount: i128,
+    currency: Currency,
+}
+
+impl Money {
+    pub fn zero(currency: Currency) -> Self {
+        Self {
+            amount: 0,
+            currency,
+        }
+    }
+
+    /// Creates an amount from a whole number of the currency's minor unit,
+    /// such as pence
+    pub fn from_minor_units(minor_units: i64, currency: Currency) -> Self {
+        Self {
+            amount: i128::from(minor_units) * minor_unit(currency),
+            currency,
+        }
+    }
+
+    /// Creates an amount from a floating point number of the currency's major
+    /// unit, rounding it to the nearest billionth
+    ///
+    /// Costs are worked out in floating point from kWh and rates, then brought
+    /// to fixed precision here. Values that are not finite become zero.
+    pub fn from_f64(amount: f64, currency: Currency) -> Self {
+        let amount = if amount.is_finite() {
+            (amount * SCALE as f64).round() as i128
+        } else {
+            0
+        };
+        Self { amount, currency }
+    }
+
+    pub fn currency(self) -> Currency {
+        self.currency
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.amount as f64 / SCALE as f64
+    }
+
+    /// Rounds to the currency's minor unit, with halves rounded away from zero
+    pub fn round(self) -> Self {
+        let unit = minor_unit(self.currency);
+        let remainder = self.amount % unit;
+        let mut amount = self.amount - remainder;
+        if remainder.abs() * 2 >= unit {
+            amount += unit * remainder.signum();
+        }
+        Self { amount, ..self }
+    }
+
+    /// Returns the amount as a whole number of the currency's minor unit,
+    /// rounding it first
+    pub fn minor_units(self) -> i64 {
+        (self.round().amount / minor_unit(self.currency)) as i64
+    }
+
+    /// Adds up amounts in a currency, giving zero when there are none
+    pub fn sum(currency: Currency, amounts: impl IntoIterator<Item = Money>) -> Self {
+        amounts
+            .into_iter()
+            .fold(Self::zero(currency), |total, amount| total + amount)
+    }
+
+    /// Adds up amounts that should all be in `currency`
+    ///
+    /// # Returns
+    /// The sum, or `None` when any amount is in another currency, as there is
+    /// no exchange rate to convert it with
+    pub fn sum_in(currency: Currency, amounts: impl IntoIterator<Item = Money>) -> Option<Self> {
+        amounts
+            .into_iter()
+            .try_fold(Self::zero(currency), |total, amount| {
+                (amount.currency == currency).then(|| total + amount)
+            })
+    }
+
+    fn same_currency(self, other: Self) -> Currency {
+        assert_eq!(
+            self.currency, other.currency,
+            "cannot combine amounts in different currencies"
+        );
+        self.currency
+    }
+}
+
+fn minor_unit(currency: Currency) -> i128 {
+    10i128.pow(FRACTION_DIGITS - currency.minor_digits())
+}
+
+impl Add for Money {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            currency: self.same_currency(other),
+            amount: self.amount + other.amount,
+        }
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Money {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self + -other
+    }
+}
+
+impl Neg for Money {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            amount: -self.amount,
+            ..self
+        }
+    }
+}
+
+/// Scales an amount, such as a net cost by a tax rate, to the nearest billionth
+impl Mul<f64> for Money {
+    type Output = Self;
+
+    fn mul(self, factor: f64) -> Self {
+        Self::from_f64(self.to_f64() * factor, self.currency)
+    }
+}
+
+/// Amounts are ordered by value; comparing different currencies panics
+impl PartialOrd for Money {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Money {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.same_currency(*other);
+        self.amount.cmp(&other.amount)
+    }
+}
+
+/// Writes the amount as a decimal with at least the currency's minor digits
+/// and no trailing zeros beyond them, e.g. `12.50` or `0.040325`
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.amount < 0 { "-" } else { "" };
+        let magnitude = self.amount.unsigned_abs();
+        let whole = magnitude / SCALE as u128;
+        let mut fraction = format!(
+            "{:0width$}",
+            magnitude % SCALE as u128,
+            width = FRACTION_DIGITS as usize
+        );
+        while fraction.len() > self.currency.minor_digits() as usize && fraction.ends_with('0') {
+            fraction.pop();
+        }
+        write!(f, "{sign}{whole}")?;
+        if !fraction.is_empty() {
+            write!(f, ".{fraction}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Serialises as `{"amount": "12.50", "currency": "GBP"}`, with the amount as
+/// a string so that clients do not read it into a float
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut money = serializer.serialize_struct("Money", 2)?;
+        money.serialize_field("amount", &self.to_string())?;
+        money.serialize_field("currency", &self.currency)?;
+        money.end()
+    }
+}
+
+/// When the lines of a bill are rounded to the currency's minor unit
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Rounding {
+    /// Each line is rounded and the total is the sum of the rounded lines,
+    /// so the lines always add up to it
+    #[default]
+    PerLine,
+    /// Lines are rounded for display, but the total is their exact sum
+    /// rounded once, so it may differ from the rounded lines by a minor unit
+    PerBill,
+}
+
+impl Rounding {
+    /// Adds up the exact amounts of a bill's lines, rounding as chosen
+    pub fn total(self, currency: Currency, lines: impl IntoIterator<Item = Money>) -> Money {
+        match self {
+            Self::PerLine => Money::sum(currency, lines.into_iter().map(Money::round)),
+            Self::PerBill => Money::sum(currency, lines).round(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gbp(amount: f64) -> Money {
+        Money::from_f64(amount, Currency::Gbp)
+    }
+
+    #[test]
+    fn test_sums_do_not_drift() {
+        let total = Money::sum(Currency::Gbp, (0..1000).map(|_| gbp(0.1)));
+
+        assert_eq!(total, Money::from_minor_units(10000, Currency::Gbp));
+        assert_eq!(total.to_string(), "100.00");
+    }
+
+    #[test]
+    fn test_round_half_away_from_zero() {
+        assert_eq!(gbp(0.125).round().to_string(), "0.13");
+        assert_eq!(gbp(-0.125).round().to_string(), "-0.13");
+        assert_eq!(gbp(0.124999).round().to_string(), "0.12");
+        assert_eq!(gbp(2.0 / 3.0).minor_units(), 67);
+    }
+
+    #[test]
+    fn test_display_keeps_sub_minor_digits() {
+        assert_eq!(gbp(0.040325).to_string(), "0.040325");
+        assert_eq!(gbp(-0.5).to_string(), "-0.50");
+        assert_eq!(gbp(12.0).to_string(), "12.00");
+    }
+
+    #[test]
+    fn test_serialises_amount_as_string() {
+        assert_eq!(
+            serde_json::to_value(gbp(1.5)).unwrap(),
+            serde_json::json!({"amount": "1.50", "currency": "GBP"})
+        );
+    }
+
+    #[test]
+    fn test_per_line_and_per_bill_rounding() {
+        let lines = [gbp(0.004), gbp(0.004), gbp(0.004)];
+
+        assert_eq!(
+            Rounding::PerLine.total(Currency::Gbp, lines).to_string(),
+            "0.00"
+        );
+        assert_eq!(
+            Rounding::PerBill.total(Currency::Gbp, lines).to_string(),
+            "0.01"
+        );
+    }
+
+    #[test]
+    fn test_sum_in_rejects_other_currencies() {
+        let euros = Money::from_f64(1.0, Currency::Eur);
+
+        assert_eq!(
+            Money::sum_in(Currency::Gbp, [gbp(1.0), gbp(2.0)]),
+            Some(gbp(3.0))
+        );
+        assert_eq!(Money::sum_in(Currency::Gbp, [gbp(1.0), euros]), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "different currencies")]
+    fn test_adding_different_currencies_panics() {
+        let _ = gbp(1.0) + Money::from_f64(1.0, Currency::Eur);
+    }
+}
@@ -1,35 +1,364 @@
-use crate::datastore::reading::ElectricityReading;
-use std::cmp::Ordering;
-use std::collections::HashMap;
-use time::Weekday;
+use crate::datastore::consumption::Consumption;
+use crate::datastore::money::{Currency, Money, Rounding};
+use crate::datastore::tax::{VatCategory, VatRates};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use time::{Date, Duration, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset, Weekday};
+use time_tz::{Offset, OffsetDateTimeExt, OffsetResult, PrimitiveDateTimeExt, TimeZone, Tz};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct PricePlan {
     pub supplier_id: String,
-    #[allow(dead_code)]
     pub plan_name: String,
+    /// The currency the plan's rates and charges are in
+    pub currency: Currency,
+    /// Which VAT rate the plan's charges are taxed at
+    pub vat_category: VatCategory,
     pub unit_rate: f64,
-    #[allow(dead_code)]
+    /// Fixed charge per day, whatever the consumption
+    pub standing_charge: f64,
+    /// Credit per kWh exported to the grid, paid at the same rate at any time of day
+    pub export_rate: f64,
     pub rate_multipliers: HashMap<Weekday, f64>,
+    pub rate_bands: Vec<RateBand>,
+    /// Blocks pricing the first kWh used in each billing period, in order
+    pub tiers: Vec<RateTier>,
+    /// The local time rate bands, weekdays and billing months follow
+    pub local_time: LocalTime,
+    /// Retired plans are no longer offered, but accounts already on them keep them
+    pub retired: bool,
+    /// When the plan's current rates took effect, or `None` if they always applied
+    pub valid_from: Option<OffsetDateTime>,
+    /// Rates the plan charged before its current ones, oldest first
+    pub previous_versions: Vec<RateVersion>,
+    /// Unit rates for individual half hours, keyed by the start of each, as
+    /// imported for agile tariffs
+    ///
+    /// A half hour with a price is charged at it instead of the plan's other
+    /// rates; the standing charge still applies.
+    pub half_hourly_prices: BTreeMap<OffsetDateTime, f64>,
+}
+
+/// What a consumption costs on a price plan, line by line
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CostBreakdown {
+    pub consumption_cost: Money,
+    pub standing_charge: Money,
+    pub export_credit: Money,
+    /// The consumption cost plus the standing charge, less the export credit,
+    /// before tax
+    pub total: Money,
+    /// VAT on the consumption cost and standing charge
+    pub tax: Money,
+    /// The total with VAT added
+    pub gross: Money,
 }
 
-impl Eq for PricePlan {}
+impl CostBreakdown {
+    pub fn zero(currency: Currency) -> Self {
+        Self {
+            consumption_cost: Money::zero(currency),
+            standing_charge: Money::zero(currency),
+            export_credit: Money::zero(currency),
+            total: Money::zero(currency),
+            tax: Money::zero(currency),
+            gross: Money::zero(currency),
+        }
+    }
+}
+
+/// The unit rate of an agile plan for the half hour starting at `start`
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct HalfHourPrice {
+    #[serde(with = "time::serde::rfc3339")]
+    pub start: OffsetDateTime,
+    pub unit_rate: f64,
+}
+
+impl HalfHourPrice {
+    pub fn new(start: OffsetDateTime, unit_rate: f64) -> Self {
+        Self { start, unit_rate }
+    }
+
+    /// Returns when the half hour ends, or `None` when that is past the last
+    /// representable instant
+    pub fn end(&self) -> Option<OffsetDateTime> {
+        self.start.checked_add(HALF_HOUR)
+    }
+
+    /// Whether `start` falls exactly on the hour or half past, as every
+    /// half-hourly price must
+    pub fn is_aligned(&self) -> bool {
+        half_hour_containing(self.start) == self.start
+    }
+}
 
-impl PartialEq<Self> for PricePlan {
+/// The local time a plan's rate bands, weekdays and billing months follow
+///
+/// A named time zone such as `Europe/London` follows its daylight saving
+/// rules, so a band from 00:00 to 07:00 keeps to the clock all year round. A
+/// fixed offset from UTC never changes.
+#[derive(Clone, Copy, Debug)]
+pub enum LocalTime {
+    Offset(UtcOffset),
+    Zone(&'static Tz),
+}
+
+impl LocalTime {
+    /// Looks up a time zone by its IANA name, e.g. `Europe/London`
+    pub fn zone(name: &str) -> Option<Self> {
+        time_tz::timezones::get_by_name(name).map(Self::Zone)
+    }
+
+    /// Returns an instant as the local clock shows it
+    pub fn to_local(self, instant: OffsetDateTime) -> OffsetDateTime {
+        match self {
+            Self::Offset(offset) => instant.to_offset(offset),
+            Self::Zone(zone) => instant.to_timezone(zone),
+        }
+    }
+
+    /// Returns the instant the local clock shows `time` on `date`
+    ///
+    /// When the clocks go back and the time comes round twice, this is the
+    /// first. When they go forward past it, it is the time as it would have
+    /// been read before they changed, which falls just after the change.
+    pub fn at(self, date: Date, time: Time) -> OffsetDateTime {
+        let local = PrimitiveDateTime::new(date, time);
+        match self {
+            Self::Offset(offset) => local.assume_offset(offset),
+            Self::Zone(zone) => match local.assume_timezone(zone) {
+                OffsetResult::Some(instant) | OffsetResult::Ambiguous(instant, _) => instant,
+                OffsetResult::None => {
+                    let before = local.assume_utc() - Duration::DAY;
+                    local.assume_offset(zone.get_offset_utc(&before).to_utc())
+                }
+            },
+        }
+    }
+}
+
+impl From<UtcOffset> for LocalTime {
+    fn from(offset: UtcOffset) -> Self {
+        Self::Offset(offset)
+    }
+}
+
+impl PartialEq for LocalTime {
     fn eq(&self, other: &Self) -> bool {
-        self.unit_rate == other.unit_rate
+        match (self, other) {
+            (Self::Offset(offset), Self::Offset(other)) => offset == other,
+            (Self::Zone(zone), Self::Zone(other)) => zone.name() == other.name(),
+            _ => false,
+        }
     }
 }
 
-impl PartialOrd<Self> for PricePlan {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+const HALF_HOUR: Duration = Duration::minutes(30);
+
+/// The hours in an average month, leap years included
+const HOURS_PER_MONTH: f64 = 365.25 * 24.0 / 12.0;
+
+/// Returns the start of the half hour an instant falls in
+fn half_hour_containing(instant: OffsetDateTime) -> OffsetDateTime {
+    let unix_timestamp = instant.unix_timestamp();
+    let start = unix_timestamp - unix_timestamp.rem_euclid(HALF_HOUR.whole_seconds());
+    OffsetDateTime::from_unix_timestamp(start)
+        .expect("the start of a half hour is a valid timestamp")
+        .to_offset(instant.offset())
+}
+
+/// Rates a plan charged from `valid_from` until `valid_to`, before its current rates
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateVersion {
+    pub valid_from: Option<OffsetDateTime>,
+    pub valid_to: OffsetDateTime,
+    pub unit_rate: f64,
+    pub standing_charge: f64,
+    pub export_rate: f64,
+    pub rate_multipliers: HashMap<Weekday, f64>,
+    pub rate_bands: Vec<RateBand>,
+    pub tiers: Vec<RateTier>,
+}
+
+impl RateVersion {
+    fn contains(&self, instant: OffsetDateTime) -> bool {
+        self.valid_from.is_none_or(|from| from <= instant) && instant < self.valid_to
+    }
+}
+
+/// New rates for a plan, replacing its current ones when it changes them
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateChange {
+    pub unit_rate: f64,
+    pub standing_charge: f64,
+    pub export_rate: f64,
+    pub rate_multipliers: HashMap<Weekday, f64>,
+    pub rate_bands: Vec<RateBand>,
+    pub tiers: Vec<RateTier>,
+}
+
+impl RateChange {
+    /// A flat unit rate, with no standing charge, export credit, bands or tiers
+    pub fn new(unit_rate: f64) -> Self {
+        Self {
+            unit_rate,
+            standing_charge: 0.0,
+            export_rate: 0.0,
+            rate_multipliers: HashMap::new(),
+            rate_bands: Vec::new(),
+            tiers: Vec::new(),
+        }
+    }
+}
+
+/// The rates of one version of a plan
+struct Rates<'a> {
+    unit_rate: f64,
+    standing_charge: f64,
+    export_rate: f64,
+    rate_multipliers: &'a HashMap<Weekday, f64>,
+    rate_bands: &'a [RateBand],
+    tiers: &'a [RateTier],
+}
+
+impl Rates<'_> {
+    /// Prices energy used at a local time, after `used_kwh` had already been
+    /// used in the billing period
+    ///
+    /// Whatever falls within a tier is charged at the tier's rate, and the rest
+    /// at the rate of the band covering the time of day or the plain unit rate.
+    /// Either way the weekday multiplier applies.
+    fn price(&self, local: OffsetDateTime, energy_kwh: f64, used_kwh: f64) -> f64 {
+        let mut cost = 0.0;
+        self.itemise(local, energy_kwh, used_kwh, |_, _, part| cost += part);
+        cost
+    }
+
+    /// Prices energy like [`Rates::price`], passing `charge` the rate, kWh and
+    /// cost of each part charged at a different rate
+    fn itemise(
+        &self,
+        local: OffsetDateTime,
+        energy_kwh: f64,
+        used_kwh: f64,
+        mut charge: impl FnMut(ChargeRate, f64, f64),
+    ) {
+        let multiplier = self
+            .rate_multipliers
+            .get(&local.weekday())
+            .copied()
+            .unwrap_or(1.0);
+        let mut used_kwh = used_kwh;
+        let mut remaining_kwh = energy_kwh;
+        for (index, tier) in self.tiers.iter().enumerate() {
+            let in_tier = (tier.up_to_kwh - used_kwh).clamp(0.0, remaining_kwh);
+            if in_tier > 0.0 {
+                charge(
+                    ChargeRate::Tier(index + 1),
+                    in_tier,
+                    in_tier * tier.unit_rate * multiplier,
+                );
+            }
+            used_kwh += in_tier;
+            remaining_kwh -= in_tier;
+        }
+        if remaining_kwh > 0.0 {
+            let (rate, unit_rate) = match self
+                .rate_bands
+                .iter()
+                .find(|band| band.contains(local.time()))
+            {
+                Some(band) => (ChargeRate::Band(band.name.clone()), band.unit_rate),
+                None => (ChargeRate::Standard, self.unit_rate),
+            };
+            charge(rate, remaining_kwh, remaining_kwh * unit_rate * multiplier);
+        }
     }
 }
 
-impl Ord for PricePlan {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.unit_rate.partial_cmp(&other.unit_rate).unwrap()
+/// The rate a part of a meter's consumption was charged at
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ChargeRate {
+    /// The imported price of each half hour, on agile plans
+    HalfHourly,
+    /// A block of the first kWh used in each billing period, numbered from 1
+    Tier(usize),
+    /// A time-of-use band, by name
+    Band(String),
+    /// The plan's plain unit rate
+    Standard,
+}
+
+/// Names the rate as a bill would, e.g. `tier 1` or the band's own name
+impl fmt::Display for ChargeRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::HalfHourly => f.write_str("half-hourly"),
+            Self::Tier(number) => write!(f, "tier {number}"),
+            Self::Band(name) => f.write_str(name),
+            Self::Standard => f.write_str("standard"),
+        }
+    }
+}
+
+/// The energy in a consumption charged at one rate, and what it cost
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnergyCharge {
+    pub rate: ChargeRate,
+    pub energy_kwh: f64,
+    /// The exact cost, unrounded
+    pub cost: Money,
+}
+
+/// A block of energy charged at its own unit rate, e.g. the first 100 kWh of each month
+///
+/// Tiers are cumulative: a tier covers the energy from the previous tier's
+/// `up_to_kwh`, or from nothing, up to its own within a billing period.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateTier {
+    pub up_to_kwh: f64,
+    pub unit_rate: f64,
+}
+
+impl RateTier {
+    pub fn new(up_to_kwh: f64, unit_rate: f64) -> Self {
+        Self {
+            up_to_kwh,
+            unit_rate,
+        }
+    }
+}
+
+/// A time-of-day window charged at its own unit rate, e.g. an overnight off-peak period
+///
+/// Bands are evaluated in the plan's local time. A band whose end is not after
+/// its start wraps past midnight, so `23:00`-`07:00` covers the night.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateBand {
+    pub name: String,
+    pub start: Time,
+    pub end: Time,
+    pub unit_rate: f64,
+}
+
+impl RateBand {
+    pub fn new(name: &str, start: Time, end: Time, unit_rate: f64) -> Self {
+        Self {
+            name: name.to_string(),
+            start,
+            end,
+            unit_rate,
+        }
+    }
+
+    pub fn contains(&self, time: Time) -> bool {
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
     }
 }
 
@@ -43,48 +372,857 @@ impl PricePlan {
         Self {
             supplier_id: supplier_id.to_string(),
             plan_name: plan_name.to_string(),
+            currency: Currency::default(),
+            vat_category: VatCategory::default(),
             unit_rate,
+            standing_charge: 0.0,
+            export_rate: 0.0,
             rate_multipliers,
+            rate_bands: Vec::new(),
+            tiers: Vec::new(),
+            local_time: LocalTime::Offset(UtcOffset::UTC),
+            retired: false,
+            valid_from: None,
+            previous_versions: Vec::new(),
+            half_hourly_prices: BTreeMap::new(),
+        }
+    }
+
+    /// Adds time-of-use bands to the plan, evaluated in the given local time
+    ///
+    /// Bands are checked in order and the first one covering a time of day wins.
+    /// Times outside every band are charged at the plan's `unit_rate`.
+    pub fn with_rate_bands(
+        mut self,
+        local_time: impl Into<LocalTime>,
+        rate_bands: Vec<RateBand>,
+    ) -> Self {
+        self.local_time = local_time.into();
+        self.rate_bands = rate_bands;
+        self
+    }
+
+    /// Adds blocks pricing the first kWh used in each billing period
+    ///
+    /// Energy beyond the last tier is charged at the plan's time-of-use rates.
+    pub fn with_tiers(mut self, tiers: Vec<RateTier>) -> Self {
+        self.tiers = tiers;
+        self
+    }
+
+    /// Sets the fixed charge per day
+    pub fn with_standing_charge(mut self, standing_charge: f64) -> Self {
+        self.standing_charge = standing_charge;
+        self
+    }
+
+    /// Sets the credit per kWh exported to the grid
+    pub fn with_export_rate(mut self, export_rate: f64) -> Self {
+        self.export_rate = export_rate;
+        self
+    }
+
+    /// Replaces the plan's rates from `valid_from`, keeping the current rates as
+    /// a previous version that applies until then
+    pub fn change_rates(&mut self, valid_from: OffsetDateTime, rates: RateChange) {
+        self.previous_versions.push(RateVersion {
+            valid_from: self.valid_from,
+            valid_to: valid_from,
+            unit_rate: std::mem::replace(&mut self.unit_rate, rates.unit_rate),
+            standing_charge: std::mem::replace(&mut self.standing_charge, rates.standing_charge),
+            export_rate: std::mem::replace(&mut self.export_rate, rates.export_rate),
+            rate_multipliers: std::mem::replace(&mut self.rate_multipliers, rates.rate_multipliers),
+            rate_bands: std::mem::replace(&mut self.rate_bands, rates.rate_bands),
+            tiers: std::mem::replace(&mut self.tiers, rates.tiers),
+        });
+        self.valid_from = Some(valid_from);
+    }
+
+    /// Stores half-hourly prices, replacing any the plan has for the same half hours
+    pub fn add_half_hourly_prices(&mut self, prices: Vec<HalfHourPrice>) {
+        self.half_hourly_prices.extend(
+            prices
+                .into_iter()
+                .map(|price| (price.start, price.unit_rate)),
+        );
+    }
+
+    /// Returns the imported price of the half hour an instant falls in, if any
+    pub fn half_hourly_price_at(&self, instant: OffsetDateTime) -> Option<f64> {
+        self.half_hourly_prices
+            .get(&half_hour_containing(instant))
+            .copied()
+    }
+
+    /// Returns the plan as if the rates in force at `instant` had always applied
+    ///
+    /// Useful for asking what a consumption would have cost at today's rates.
+    /// Half-hourly prices are kept, since each only ever applies to its own half hour.
+    pub fn at_rates_in_force(&self, instant: OffsetDateTime) -> Self {
+        let rates = self.rates_at(instant);
+        Self {
+            unit_rate: rates.unit_rate,
+            standing_charge: rates.standing_charge,
+            export_rate: rates.export_rate,
+            rate_multipliers: rates.rate_multipliers.clone(),
+            rate_bands: rates.rate_bands.to_vec(),
+            tiers: rates.tiers.to_vec(),
+            valid_from: None,
+            previous_versions: Vec::new(),
+            ..self.clone()
         }
     }
 
-    /// Calculates the total cost for a collection of electricity readings
+    /// Calculates the total cost of a meter's consumption on this plan,
+    /// including the standing charge for the time it covers, less the credit
+    /// for any energy exported
     ///
     /// # Arguments
-    /// * `stored_readings` - Vector of electricity readings from an account
+    /// * `consumption` - Energy consumption integrated from an account's readings
     ///
     /// # Returns
-    /// The total cost in currency units based on the plan's unit rate
-    pub fn average_hourly_cost(&self, stored_readings: &[ElectricityReading]) -> f64 {
-        if stored_readings.is_empty() {
-            return 0.0;
+    /// The exact net cost, which is negative when the export credit outweighs
+    /// the rest
+    pub fn cost(&self, consumption: &Consumption) -> Money {
+        self.consumption_cost(consumption) + self.standing_charge_for(consumption)
+            - self.export_credit(consumption)
+    }
+
+    /// Breaks the cost of a meter's consumption on this plan down into the
+    /// lines of a bill, each rounded to the currency's minor unit
+    ///
+    /// VAT is charged at the plan's rate on the consumption cost and standing
+    /// charge. Export credits are not taxed.
+    ///
+    /// # Arguments
+    /// * `consumption` - Energy consumption integrated from an account's readings
+    /// * `rounding` - Whether totals add up the rounded lines or round the exact cost
+    /// * `vat_rates` - The VAT rates in force
+    pub fn cost_breakdown(
+        &self,
+        consumption: &Consumption,
+        rounding: Rounding,
+        vat_rates: &VatRates,
+    ) -> CostBreakdown {
+        let consumption_cost = self.consumption_cost(consumption);
+        let standing_charge = self.standing_charge_for(consumption);
+        let export_credit = self.export_credit(consumption);
+        let taxable = rounding.total(self.currency, [consumption_cost, standing_charge]);
+        let tax = (taxable * vat_rates.rate_for(self.vat_category)).round();
+        let total = rounding.total(
+            self.currency,
+            [consumption_cost, standing_charge, -export_credit],
+        );
+        CostBreakdown {
+            consumption_cost: consumption_cost.round(),
+            standing_charge: standing_charge.round(),
+            export_credit: export_credit.round(),
+            total,
+            tax,
+            gross: total + tax,
         }
-        let average_reading = Self::average_reading(stored_readings);
-        let hours_elapsed = Self::total_hours_elapsed(stored_readings);
+    }
+
+    /// Calculates the cost of the energy in a meter's consumption on this plan
+    ///
+    /// Every consumption interval is split wherever the rate can change (local
+    /// midnight, band boundaries and changes between versions of the plan),
+    /// and the energy used in each part is priced at the rate in force at the time.
+    /// Tiers are filled in the order energy was used, starting again at the
+    /// beginning of each billing period, which is a calendar month in the
+    /// plan's local time. Half hours with an imported price are charged at it.
+    pub fn consumption_cost(&self, consumption: &Consumption) -> Money {
+        Money::from_f64(self.energy_charge(consumption), self.currency)
+    }
+
+    fn energy_charge(&self, consumption: &Consumption) -> f64 {
+        let mut cost = 0.0;
+        self.charge_energy(consumption, |_, _, part| cost += part);
+        cost
+    }
+
+    /// Breaks the cost of the energy in a meter's consumption on this plan
+    /// down by the rate each part of it was charged at
+    ///
+    /// # Returns
+    /// The kWh charged at each rate and their exact cost, in the order the
+    /// rates were first charged. Adding up the costs gives the consumption cost.
+    pub fn energy_charges(&self, consumption: &Consumption) -> Vec<EnergyCharge> {
+        let mut charges: Vec<(ChargeRate, f64, f64)> = Vec::new();
+        self.charge_energy(consumption, |rate, energy_kwh, cost| {
+            match charges.iter_mut().find(|(charged, _, _)| *charged == rate) {
+                Some((_, total_kwh, total_cost)) => {
+                    *total_kwh += energy_kwh;
+                    *total_cost += cost;
+                }
+                None => charges.push((rate, energy_kwh, cost)),
+            }
+        });
+        charges
+            .into_iter()
+            .map(|(rate, energy_kwh, cost)| EnergyCharge {
+                rate,
+                energy_kwh,
+                cost: Money::from_f64(cost, self.currency),
+            })
+            .collect()
+    }
 
-        let average_hourly_usage = average_reading / hours_elapsed;
-        average_hourly_usage * self.unit_rate
+    /// Prices every part of a consumption, passing `charge` the rate, kWh and
+    /// cost of each
+    fn charge_energy(&self, consumption: &Consumption, charge: impl FnMut(ChargeRate, f64, f64)) {
+        self.charge_scaled_energy(consumption, 1.0, true, charge);
     }
 
-    fn average_reading(stored_readings: &[ElectricityReading]) -> f64 {
-        if stored_readings.is_empty() {
-            return 0.0;
+    /// Prices every part of a consumption with its energy multiplied by
+    /// `scale`, filling tiers afresh each calendar month when `monthly` is set
+    /// and only once otherwise
+    fn charge_scaled_energy(
+        &self,
+        consumption: &Consumption,
+        scale: f64,
+        monthly: bool,
+        mut charge: impl FnMut(ChargeRate, f64, f64),
+    ) {
+        let mut billing_period = None;
+        let mut used_kwh = 0.0;
+        for interval in &consumption.intervals {
+            for (start, end) in self.split_at_rate_changes(interval.start, interval.end) {
+                let local = self.local_time.to_local(start);
+                let period = monthly.then_some((local.year(), local.month()));
+                if billing_period != Some(period) {
+                    billing_period = Some(period);
+                    used_kwh = 0.0;
+                }
+                let energy_kwh = interval.energy_between(start, end) * scale;
+                match self.half_hourly_price_at(start) {
+                    Some(unit_rate) => {
+                        charge(ChargeRate::HalfHourly, energy_kwh, energy_kwh * unit_rate)
+                    }
+                    None => self
+                        .rates_at(start)
+                        .itemise(local, energy_kwh, used_kwh, &mut charge),
+                }
+                used_kwh += energy_kwh;
+            }
         }
-        let readings_sum: f64 = stored_readings.iter().map(|r| r.reading).sum();
-        readings_sum / stored_readings.len() as f64
     }
 
-    fn total_hours_elapsed(stored_readings: &[ElectricityReading]) -> f64 {
-        if stored_readings.is_empty() {
-            return 0.0;
+    /// Calculates the standing charge for the time a meter's consumption covers
+    ///
+    /// The daily charge is prorated, so twelve hours of readings are charged
+    /// half a day, each part at the standing charge in force at the time.
+    pub fn standing_charge_for(&self, consumption: &Consumption) -> Money {
+        Money::from_f64(self.daily_charges(consumption), self.currency)
+    }
+
+    fn daily_charges(&self, consumption: &Consumption) -> f64 {
+        consumption
+            .intervals
+            .iter()
+            .map(|interval| self.daily_charges_between(interval.start, interval.end))
+            .sum()
+    }
+
+    /// Calculates the standing charge for the time from `from` until `to`,
+    /// whatever readings there are
+    ///
+    /// Each part of the time is charged at the standing charge in force then.
+    pub fn standing_charge_between(&self, from: OffsetDateTime, to: OffsetDateTime) -> Money {
+        Money::from_f64(self.daily_charges_between(from, to), self.currency)
+    }
+
+    fn daily_charges_between(&self, from: OffsetDateTime, to: OffsetDateTime) -> f64 {
+        self.split_at_rate_changes(from, to)
+            .into_iter()
+            .map(|(start, end)| {
+                let days = (end - start).as_seconds_f64() / Duration::DAY.as_seconds_f64();
+                days * self.rates_at(start).standing_charge
+            })
+            .sum()
+    }
+
+    /// Calculates the credit for the energy a meter exported to the grid
+    ///
+    /// Each part of an export is credited at the export rate in force at the time.
+    pub fn export_credit(&self, consumption: &Consumption) -> Money {
+        Money::from_f64(self.export_credits(consumption), self.currency)
+    }
+
+    fn export_credits(&self, consumption: &Consumption) -> f64 {
+        consumption
+            .exports
+            .iter()
+            .flat_map(|interval| {
+                self.split_at_rate_changes(interval.start, interval.end)
+                    .into_iter()
+                    .map(move |(start, end)| (interval, start, end))
+            })
+            .map(|(interval, start, end)| {
+                interval.energy_between(start, end) * self.rates_at(start).export_rate
+            })
+            .sum()
+    }
+
+    /// Calculates the average cost per hour of a meter's consumption on this plan
+    ///
+    /// # Returns
+    /// The net cost, standing charge included and export credit deducted,
+    /// divided by the hours covered by the readings, or zero when the readings
+    /// do not span any time. The cost is divided before it is brought to fixed
+    /// precision, so a short span does not magnify its last digit.
+    pub fn average_hourly_cost(&self, consumption: &Consumption) -> Money {
+        let hours_elapsed = consumption.total_hours();
+        if hours_elapsed == 0.0 {
+            return Money::zero(self.currency);
+        }
+        let cost = self.energy_charge(consumption) + self.daily_charges(consumption)
+            - self.export_credits(consumption);
+        Money::from_f64(cost / hours_elapsed, self.currency)
+    }
+
+    /// Projects what a year of consumption like a meter's would cost on this
+    /// plan, VAT included
+    ///
+    /// The readings are scaled to an average month of a 365.25-day year, so
+    /// the kWh used in it fill the plan's tiers as a month's would, and twelve
+    /// of those months make the year. The projection is only as
+    /// representative as the readings are.
+    ///
+    /// # Returns
+    /// The projected gross cost, unrounded, or zero when the readings do not
+    /// span any time
+    pub fn projected_annual_cost(&self, consumption: &Consumption, vat_rates: &VatRates) -> Money {
+        let hours_elapsed = consumption.total_hours();
+        if hours_elapsed == 0.0 {
+            return Money::zero(self.currency);
+        }
+        let scale = HOURS_PER_MONTH / hours_elapsed;
+        let mut energy_charge = 0.0;
+        self.charge_scaled_energy(consumption, scale, false, |_, _, part| {
+            energy_charge += part
+        });
+        let taxable = energy_charge + self.daily_charges(consumption) * scale;
+        let gross = taxable * (1.0 + vat_rates.rate_for(self.vat_category))
+            - self.export_credits(consumption) * scale;
+        Money::from_f64(gross * 12.0, self.currency)
+    }
+
+    /// Returns the unit rate charged on the given day of the week
+    ///
+    /// Days without an explicit multiplier are charged at the plain unit rate.
+    pub fn unit_rate_on(&self, weekday: Weekday) -> f64 {
+        self.unit_rate * self.multiplier_on(weekday)
+    }
+
+    /// Returns the unit rate charged at the given instant for the first kWh of
+    /// a billing period
+    ///
+    /// The imported price of the half hour, if there is one. Otherwise the rate
+    /// of the first tier, or else of the band covering the local time of day,
+    /// or else the plain unit rate, scaled by the multiplier for the local weekday.
+    pub fn unit_rate_at(&self, instant: OffsetDateTime) -> f64 {
+        let local = self.local_time.to_local(instant);
+        self.half_hourly_price_at(instant)
+            .unwrap_or_else(|| self.rates_at(instant).price(local, 1.0, 0.0))
+    }
+
+    pub fn rate_band_at(&self, time: Time) -> Option<&RateBand> {
+        self.rate_bands.iter().find(|band| band.contains(time))
+    }
+
+    fn multiplier_on(&self, weekday: Weekday) -> f64 {
+        self.rate_multipliers.get(&weekday).copied().unwrap_or(1.0)
+    }
+
+    fn rates_at(&self, instant: OffsetDateTime) -> Rates<'_> {
+        match self
+            .previous_versions
+            .iter()
+            .find(|version| version.contains(instant))
+        {
+            Some(version) => Rates {
+                unit_rate: version.unit_rate,
+                standing_charge: version.standing_charge,
+                export_rate: version.export_rate,
+                rate_multipliers: &version.rate_multipliers,
+                rate_bands: &version.rate_bands,
+                tiers: &version.tiers,
+            },
+            None => Rates {
+                unit_rate: self.unit_rate,
+                standing_charge: self.standing_charge,
+                export_rate: self.export_rate,
+                rate_multipliers: &self.rate_multipliers,
+                rate_bands: &self.rate_bands,
+                tiers: &self.tiers,
+            },
+        }
+    }
+
+    /// Returns the instants between `from` and `to` at which the plan moves
+    /// from one version of its rates to another, in order
+    pub fn rate_changes_between(
+        &self,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+    ) -> Vec<OffsetDateTime> {
+        let mut changes = self
+            .version_changes()
+            .filter(|change| *change > from && *change < to)
+            .collect::<Vec<OffsetDateTime>>();
+        changes.sort();
+        changes.dedup();
+        changes
+    }
+
+    fn version_changes(&self) -> impl Iterator<Item = OffsetDateTime> + '_ {
+        self.previous_versions
+            .iter()
+            .flat_map(|version| [version.valid_from, Some(version.valid_to)])
+            .chain([self.valid_from])
+            .flatten()
+    }
+
+    fn split_at_rate_changes(
+        &self,
+        start: OffsetDateTime,
+        end: OffsetDateTime,
+    ) -> Vec<(OffsetDateTime, OffsetDateTime)> {
+        let mut intervals = Vec::new();
+        let mut current = start;
+        while current < end {
+            let interval_end = self.next_rate_change(current).min(end);
+            intervals.push((current, interval_end));
+            current = interval_end;
+        }
+        intervals
+    }
+
+    fn next_rate_change(&self, instant: OffsetDateTime) -> OffsetDateTime {
+        let local = self.local_time.to_local(instant);
+        let mut next = self
+            .local_time
+            .at(local.date() + Duration::DAY, Time::MIDNIGHT);
+        for band in self.rates_at(instant).rate_bands {
+            for boundary in [band.start, band.end] {
+                let candidate = self.local_time.at(local.date(), boundary);
+                if candidate > local && candidate < next {
+                    next = candidate;
+                }
+            }
+        }
+        let next_half_hour = (!self.half_hourly_prices.is_empty())
+            .then(|| half_hour_containing(instant) + HALF_HOUR);
+        for change in self.version_changes().chain(next_half_hour) {
+            if change > instant && change < next {
+                next = change;
+            }
         }
-        let earliest = stored_readings.iter().min_by_key(|r| r.time).unwrap().time;
-        let latest = stored_readings.iter().max_by_key(|r| r.time).unwrap().time;
+        next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datastore::consumption::IntegrationMethod;
+    use crate::datastore::reading::ElectricityReading;
+    use time::macros::{datetime, offset, time};
+
+    fn reading(time: OffsetDateTime, reading: f64) -> ElectricityReading {
+        ElectricityReading::new(time, reading)
+    }
+
+    fn gbp(amount: f64) -> Money {
+        Money::from_f64(amount, Currency::Gbp)
+    }
+
+    fn consumption(readings: &[ElectricityReading]) -> Consumption {
+        Consumption::from_readings(readings, IntegrationMethod::Trapezoidal)
+    }
+
+    fn weekend_discount_plan() -> PricePlan {
+        PricePlan::new(
+            "plan-weekend",
+            "Weekend Saver",
+            10.0,
+            HashMap::from([(Weekday::Saturday, 0.5), (Weekday::Sunday, 0.5)]),
+        )
+    }
+
+    #[test]
+    fn test_unit_rate_on_applies_multiplier() {
+        let plan = weekend_discount_plan();
+
+        assert_eq!(plan.unit_rate_on(Weekday::Friday), 10.0);
+        assert_eq!(plan.unit_rate_on(Weekday::Saturday), 5.0);
+    }
+
+    #[test]
+    fn test_cost_without_multipliers() {
+        let plan = PricePlan::new("plan-flat", "Flat", 10.0, HashMap::new());
+        let readings = vec![
+            reading(datetime!(2020-11-29 08:00:00 UTC), 1.0),
+            reading(datetime!(2020-11-29 09:00:00 UTC), 3.0),
+        ];
+
+        assert_eq!(plan.cost(&consumption(&readings)), gbp(20.0));
+        assert_eq!(plan.average_hourly_cost(&consumption(&readings)), gbp(20.0));
+    }
+
+    #[test]
+    fn test_cost_on_weekend() {
+        let plan = weekend_discount_plan();
+        // 2020-11-29 is a Sunday, 2 kWh at half the unit rate
+        let readings = vec![
+            reading(datetime!(2020-11-29 08:00:00 UTC), 1.0),
+            reading(datetime!(2020-11-29 09:00:00 UTC), 3.0),
+        ];
+
+        assert_eq!(plan.cost(&consumption(&readings)), gbp(10.0));
+    }
+
+    #[test]
+    fn test_interval_crossing_midnight_is_split_across_days() {
+        let plan = weekend_discount_plan();
+        // Sunday 23:00 to Monday 01:00, 2 kWh at the weekend rate and 2 kWh at the full rate
+        let readings = vec![
+            reading(datetime!(2020-11-29 23:00:00 UTC), 2.0),
+            reading(datetime!(2020-11-30 01:00:00 UTC), 2.0),
+        ];
+
+        assert_eq!(plan.cost(&consumption(&readings)), gbp(30.0));
+    }
+
+    #[test]
+    fn test_unsorted_readings_are_priced_chronologically() {
+        let plan = weekend_discount_plan();
+        let readings = vec![
+            reading(datetime!(2020-11-30 01:00:00 UTC), 2.0),
+            reading(datetime!(2020-11-29 23:00:00 UTC), 2.0),
+        ];
+
+        assert_eq!(plan.cost(&consumption(&readings)), gbp(30.0));
+    }
+
+    fn economy_seven_plan() -> PricePlan {
+        PricePlan::new("plan-e7", "Economy 7", 10.0, HashMap::new()).with_rate_bands(
+            UtcOffset::UTC,
+            vec![RateBand::new("off-peak", time!(00:00), time!(07:00), 4.0)],
+        )
+    }
+
+    #[test]
+    fn test_rate_band_wrapping_midnight() {
+        let band = RateBand::new("night", time!(23:00), time!(07:00), 4.0);
+
+        assert!(band.contains(time!(23:30)));
+        assert!(band.contains(time!(06:59)));
+        assert!(!band.contains(time!(07:00)));
+        assert!(!band.contains(time!(12:00)));
+    }
+
+    #[test]
+    fn test_unit_rate_at_uses_band_rate() {
+        let plan = economy_seven_plan();
+
+        assert_eq!(plan.unit_rate_at(datetime!(2020-11-30 03:00:00 UTC)), 4.0);
+        assert_eq!(plan.unit_rate_at(datetime!(2020-11-30 12:00:00 UTC)), 10.0);
+    }
+
+    #[test]
+    fn test_unit_rate_at_evaluates_bands_in_local_time() {
+        let plan = PricePlan::new("plan-peak", "Peak", 10.0, HashMap::new()).with_rate_bands(
+            offset!(+2),
+            vec![RateBand::new("peak", time!(16:00), time!(19:00), 30.0)],
+        );
+
+        assert_eq!(plan.unit_rate_at(datetime!(2020-11-30 14:30:00 UTC)), 30.0);
+        assert_eq!(plan.unit_rate_at(datetime!(2020-11-30 17:30:00 UTC)), 10.0);
+    }
+
+    #[test]
+    fn test_bands_follow_daylight_saving_time() {
+        let plan = PricePlan::new("plan-e7", "Economy 7", 10.0, HashMap::new()).with_rate_bands(
+            LocalTime::zone("Europe/London").unwrap(),
+            vec![RateBand::new("off-peak", time!(00:00), time!(07:00), 4.0)],
+        );
+        // The clocks go forward at 01:00 UTC on 2024-03-31, so the night's
+        // first 6 kWh are off-peak and the last one, at 07:00 BST, is not
+        let readings = vec![
+            reading(datetime!(2024-03-31 00:00:00 UTC), 1.0),
+            reading(datetime!(2024-03-31 07:00:00 UTC), 1.0),
+        ];
+
+        assert_eq!(plan.unit_rate_at(datetime!(2024-01-15 06:30:00 UTC)), 4.0);
+        assert_eq!(plan.unit_rate_at(datetime!(2024-07-15 06:30:00 UTC)), 10.0);
+        assert_eq!(plan.unit_rate_at(datetime!(2024-07-15 23:30:00 UTC)), 4.0);
+        assert_eq!(plan.cost(&consumption(&readings)), gbp(34.0));
+    }
+
+    #[test]
+    fn test_interval_crossing_band_boundary_is_split() {
+        let plan = economy_seven_plan();
+        // 06:00 to 08:00, 2 kWh off-peak and 2 kWh at the standard rate
+        let readings = vec![
+            reading(datetime!(2020-11-30 06:00:00 UTC), 2.0),
+            reading(datetime!(2020-11-30 08:00:00 UTC), 2.0),
+        ];
+
+        assert_eq!(plan.cost(&consumption(&readings)), gbp(28.0));
+    }
+
+    fn repriced_plan() -> PricePlan {
+        let mut plan = PricePlan::new("plan-flat", "Flat", 10.0, HashMap::new());
+        plan.change_rates(datetime!(2020-11-29 09:00:00 UTC), RateChange::new(20.0));
+        plan
+    }
+
+    #[test]
+    fn test_unit_rate_at_uses_version_in_force() {
+        let plan = repriced_plan();
+
+        assert_eq!(plan.unit_rate_at(datetime!(2020-11-29 08:59:00 UTC)), 10.0);
+        assert_eq!(plan.unit_rate_at(datetime!(2020-11-29 09:00:00 UTC)), 20.0);
+        assert_eq!(plan.unit_rate, 20.0);
+    }
+
+    #[test]
+    fn test_interval_crossing_rate_change_is_split() {
+        let plan = repriced_plan();
+        // 08:00 to 10:00, 1 kWh at the old rate and 1 kWh at the new one
+        let readings = vec![
+            reading(datetime!(2020-11-29 08:00:00 UTC), 1.0),
+            reading(datetime!(2020-11-29 10:00:00 UTC), 1.0),
+        ];
+
+        assert_eq!(plan.cost(&consumption(&readings)), gbp(30.0));
+        assert_eq!(
+            plan.at_rates_in_force(datetime!(2020-11-30 00:00:00 UTC))
+                .cost(&consumption(&readings)),
+            gbp(40.0)
+        );
+        assert_eq!(
+            plan.at_rates_in_force(datetime!(2020-11-29 00:00:00 UTC))
+                .cost(&consumption(&readings)),
+            gbp(20.0)
+        );
+    }
+
+    #[test]
+    fn test_standing_charge_is_prorated() {
+        let mut plan =
+            PricePlan::new("plan-flat", "Flat", 1.0, HashMap::new()).with_standing_charge(48.0);
+        plan.change_rates(
+            datetime!(2020-11-29 09:00:00 UTC),
+            RateChange {
+                standing_charge: 96.0,
+                ..RateChange::new(1.0)
+            },
+        );
+        // 08:00 to 10:00, an hour at each standing charge
+        let readings = vec![
+            reading(datetime!(2020-11-29 08:00:00 UTC), 1.0),
+            reading(datetime!(2020-11-29 10:00:00 UTC), 1.0),
+        ];
+
+        assert_eq!(plan.consumption_cost(&consumption(&readings)), gbp(2.0));
+        assert_eq!(plan.standing_charge_for(&consumption(&readings)), gbp(6.0));
+        assert_eq!(plan.cost(&consumption(&readings)), gbp(8.0));
+        assert_eq!(plan.average_hourly_cost(&consumption(&readings)), gbp(4.0));
+    }
+
+    fn tiered_plan() -> PricePlan {
+        PricePlan::new("plan-tiered", "Tiered", 20.0, HashMap::new())
+            .with_tiers(vec![RateTier::new(1.0, 5.0), RateTier::new(2.0, 10.0)])
+    }
+
+    #[test]
+    fn test_tiers_fill_in_order() {
+        let plan = tiered_plan();
+        // 4 kWh over four hours: 1 kWh at 5, 1 kWh at 10 and 2 kWh at 20
+        let readings = vec![
+            reading(datetime!(2020-11-29 08:00:00 UTC), 1.0),
+            reading(datetime!(2020-11-29 12:00:00 UTC), 1.0),
+        ];
+
+        assert_eq!(plan.cost(&consumption(&readings)), gbp(55.0));
+        assert_eq!(plan.unit_rate_at(datetime!(2020-11-29 08:00:00 UTC)), 5.0);
+    }
+
+    #[test]
+    fn test_tiers_restart_each_month() {
+        let plan = tiered_plan();
+        // 1 kWh on each side of midnight at the end of November
+        let readings = vec![
+            reading(datetime!(2020-11-30 23:00:00 UTC), 1.0),
+            reading(datetime!(2020-12-01 01:00:00 UTC), 1.0),
+        ];
+
+        assert_eq!(plan.cost(&consumption(&readings)), gbp(10.0));
+    }
+
+    #[test]
+    fn test_energy_charges_are_itemised_by_rate() {
+        let plan = tiered_plan().with_rate_bands(
+            UtcOffset::UTC,
+            vec![RateBand::new("off-peak", time!(00:00), time!(07:00), 4.0)],
+        );
+        // 04:00 to 09:00 at 1 kW: both tiers fill off-peak, then 1 kWh more
+        // off-peak and 2 kWh at the standard rate
+        let readings = vec![
+            reading(datetime!(2020-11-30 04:00:00 UTC), 1.0),
+            reading(datetime!(2020-11-30 09:00:00 UTC), 1.0),
+        ];
+
+        let charges = plan
+            .energy_charges(&consumption(&readings))
+            .into_iter()
+            .map(|charge| (charge.rate.to_string(), charge.energy_kwh, charge.cost))
+            .collect::<Vec<(String, f64, Money)>>();
+
+        assert_eq!(
+            charges,
+            vec![
+                ("tier 1".to_string(), 1.0, gbp(5.0)),
+                ("tier 2".to_string(), 1.0, gbp(10.0)),
+                ("off-peak".to_string(), 1.0, gbp(4.0)),
+                ("standard".to_string(), 2.0, gbp(40.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_half_hourly_prices_override_rates() {
+        let mut plan = PricePlan::new("plan-agile", "Agile", 10.0, HashMap::new());
+        plan.add_half_hourly_prices(vec![
+            HalfHourPrice::new(datetime!(2020-11-29 08:00:00 UTC), 4.0),
+            HalfHourPrice::new(datetime!(2020-11-29 08:30:00 UTC), -2.0),
+        ]);
+        // 08:00 to 09:30 at 2 kW: 1 kWh in each half hour, the last one unpriced
+        let readings = vec![
+            reading(datetime!(2020-11-29 08:00:00 UTC), 2.0),
+            reading(datetime!(2020-11-29 09:30:00 UTC), 2.0),
+        ];
+
+        assert_eq!(plan.cost(&consumption(&readings)), gbp(12.0));
+        assert_eq!(plan.unit_rate_at(datetime!(2020-11-29 08:45:00 UTC)), -2.0);
+        assert!(HalfHourPrice::new(datetime!(2020-11-29 08:30:00 +01:00), 1.0).is_aligned());
+        assert!(!HalfHourPrice::new(datetime!(2020-11-29 08:15:00 UTC), 1.0).is_aligned());
+    }
+
+    #[test]
+    fn test_export_credit_is_deducted_from_cost() {
+        let plan = PricePlan::new("plan-solar", "Solar", 10.0, HashMap::new())
+            .with_standing_charge(24.0)
+            .with_export_rate(5.0);
+        // 2 kWh imported and 3 kWh exported over two hours
+        let readings = vec![
+            reading(datetime!(2020-11-29 08:00:00 UTC), 1.0).with_export(1.0),
+            reading(datetime!(2020-11-29 10:00:00 UTC), 1.0).with_export(2.0),
+        ];
+
+        assert_eq!(plan.export_credit(&consumption(&readings)), gbp(15.0));
+        assert_eq!(plan.cost(&consumption(&readings)), gbp(7.0));
+    }
+
+    #[test]
+    fn test_export_credit_follows_rate_changes() {
+        let mut plan =
+            PricePlan::new("plan-solar", "Solar", 10.0, HashMap::new()).with_export_rate(5.0);
+        plan.change_rates(
+            datetime!(2020-11-29 09:00:00 UTC),
+            RateChange {
+                export_rate: 15.0,
+                ..RateChange::new(10.0)
+            },
+        );
+        // 08:00 to 10:00 exporting 1 kW, an hour at each export rate
+        let readings = vec![
+            reading(datetime!(2020-11-29 08:00:00 UTC), 0.0).with_export(1.0),
+            reading(datetime!(2020-11-29 10:00:00 UTC), 0.0).with_export(1.0),
+        ];
+
+        assert_eq!(plan.export_credit(&consumption(&readings)), gbp(20.0));
+        assert_eq!(
+            plan.at_rates_in_force(datetime!(2020-11-29 08:00:00 UTC))
+                .export_credit(&consumption(&readings)),
+            gbp(10.0)
+        );
+    }
+
+    #[test]
+    fn test_cost_breakdown_rounding() {
+        let plan = PricePlan::new("plan-cheap", "Cheap", 0.004, HashMap::new())
+            .with_standing_charge(0.096);
+        // 1 kWh over an hour: 0.004 for the energy and 0.004 standing charge
+        let readings = vec![
+            reading(datetime!(2020-11-29 08:00:00 UTC), 1.0),
+            reading(datetime!(2020-11-29 09:00:00 UTC), 1.0),
+        ];
+
+        let vat_rates = VatRates::default();
+        let per_line = plan.cost_breakdown(&consumption(&readings), Rounding::PerLine, &vat_rates);
+        let per_bill = plan.cost_breakdown(&consumption(&readings), Rounding::PerBill, &vat_rates);
+
+        assert_eq!(per_line.consumption_cost, gbp(0.0));
+        assert_eq!(per_line.total, gbp(0.0));
+        assert_eq!(per_bill.standing_charge, gbp(0.0));
+        assert_eq!(per_bill.total, gbp(0.01));
+    }
+
+    #[test]
+    fn test_projected_annual_cost_includes_vat() {
+        let plan = PricePlan::new("plan-solar", "Solar", 10.0, HashMap::new())
+            .with_standing_charge(24.0)
+            .with_export_rate(5.0);
+        // An hour costing 10 for energy and 1 standing charge, with 5% VAT,
+        // and 1 kWh exported
+        let readings = vec![
+            reading(datetime!(2020-11-29 08:00:00 UTC), 1.0).with_export(1.0),
+            reading(datetime!(2020-11-29 09:00:00 UTC), 1.0).with_export(1.0),
+        ];
+
+        assert_eq!(
+            plan.projected_annual_cost(&consumption(&readings), &VatRates::default()),
+            gbp((11.0 * 1.05 - 5.0) * 8766.0)
+        );
+        assert_eq!(
+            plan.projected_annual_cost(&consumption(&readings[..1]), &VatRates::default()),
+            gbp(0.0)
+        );
+    }
+
+    #[test]
+    fn test_projected_annual_cost_fills_tiers_each_month() {
+        let plan = tiered_plan();
+        // An hour at 1 kW, scaled to 730.5 kWh a month: 1 kWh at 5, 1 kWh at
+        // 10 and the rest at 20, with 5% VAT
+        let readings = vec![
+            reading(datetime!(2020-11-29 08:00:00 UTC), 1.0),
+            reading(datetime!(2020-11-29 09:00:00 UTC), 1.0),
+        ];
+
+        assert_eq!(
+            plan.projected_annual_cost(&consumption(&readings), &VatRates::default()),
+            gbp((5.0 + 10.0 + 728.5 * 20.0) * 1.05 * 12.0)
+        );
+    }
+
+    #[test]
+    fn test_weekday_multiplier_applies_to_band_rate() {
+        let plan = PricePlan::new(
+            "plan-e7-weekend",
+            "Economy 7 Weekend",
+            10.0,
+            HashMap::from([(Weekday::Sunday, 0.5)]),
+        )
+        .with_rate_bands(
+            UtcOffset::UTC,
+            vec![RateBand::new("off-peak", time!(00:00), time!(07:00), 4.0)],
+        );
 
-        // Convert time difference to fractional hours
-        // by dividing total seconds by 3600 (seconds per hour).
-        // Using seconds provides more precise calculations than `num_hours()`
-        // which truncates partial hours.
-        ((latest - earliest).whole_seconds() as f64) / 3600.0
+        assert_eq!(plan.unit_rate_at(datetime!(2020-11-29 03:00:00 UTC)), 2.0);
     }
 }
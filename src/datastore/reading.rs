@@ -4,13 +4,28 @@ use time::{Duration, OffsetDateTime};
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct ElectricityReading {
+    #[serde(with = "time::serde::rfc3339")]
     pub time: OffsetDateTime,
+    /// Power drawn from the grid, in kW
     pub reading: f64,
+    /// Power fed back into the grid, in kW, for meters that record generation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub export: Option<f64>,
 }
 
 impl ElectricityReading {
     pub fn new(time: OffsetDateTime, reading: f64) -> Self {
-        Self { time, reading }
+        Self {
+            time,
+            reading,
+            export: None,
+        }
+    }
+
+    /// Records the power being exported when the reading was taken
+    pub fn with_export(mut self, export: f64) -> Self {
+        self.export = Some(export);
+        self
     }
 
     /// Generates a collection of electricity readings for testing purposes
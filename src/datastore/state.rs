@@ -1,69 +1,83 @@
-use crate::datastore::account::Account;
-use crate::datastore::plan::PricePlan;
+use crate::config::{CatalogueSource, Config, RequestLimits, StorageBackend};
+use crate::datastore::catalogue::Catalogue;
+use crate::datastore::error::DataStoreError;
+use crate::datastore::file::FileDataStore;
 use crate::datastore::reading::ElectricityReading;
-use crate::datastore::store::DataStore;
+use crate::datastore::store::{DataStore, InMemoryDataStore};
+use crate::datastore::tax::VatRates;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
 #[derive(Clone)]
 pub struct AppState {
-    pub db: Arc<Mutex<DataStore>>,
+    pub db: Arc<Mutex<dyn DataStore>>,
+    pub limits: RequestLimits,
+    pub vat_rates: VatRates,
 }
 
-impl Default for AppState {
-    fn default() -> Self {
-        let mut accounts = HashMap::new();
+impl AppState {
+    pub fn new(data_store: impl DataStore + 'static) -> Self {
+        Self {
+            db: Arc::new(Mutex::new(data_store)),
+            limits: RequestLimits::default(),
+            vat_rates: VatRates::default(),
+        }
+    }
 
-        accounts.insert(
-            "smart-meter-0".to_string(),
-            Account::new("price-plan-0", "Sarah"),
-        );
-        accounts.insert(
-            "smart-meter-1".to_string(),
-            Account::new("price-plan-1", "Peter"),
-        );
-        accounts.insert(
-            "smart-meter-2".to_string(),
-            Account::new("price-plan-0", "Charlie"),
-        );
-        accounts.insert(
-            "smart-meter-3".to_string(),
-            Account::new("price-plan-2", "Andrea"),
-        );
-        accounts.insert(
-            "smart-meter-4".to_string(),
-            Account::new("price-plan-1", "Alex"),
-        );
+    pub fn with_limits(mut self, limits: RequestLimits) -> Self {
+        self.limits = limits;
+        self
+    }
 
-        let price_plans = vec![
-            PricePlan::new(
-                "price-plan-0",
-                "Dr Evil's Dark Energy",
-                10.0,
-                HashMap::new(),
-            ),
-            PricePlan::new("price-plan-1", "The Green Eco", 2.0, HashMap::new()),
-            PricePlan::new("price-plan-2", "Power for Everyone", 1.0, HashMap::new()),
-        ];
+    pub fn with_vat_rates(mut self, vat_rates: VatRates) -> Self {
+        self.vat_rates = vat_rates;
+        self
+    }
+}
 
-        Self {
-            db: Arc::new(Mutex::new(DataStore::new(
-                accounts,
-                HashMap::new(),
-                price_plans,
-            ))),
-        }
+impl Default for AppState {
+    fn default() -> Self {
+        let catalogue = Catalogue::demo();
+        Self::new(InMemoryDataStore::new(
+            catalogue.accounts,
+            HashMap::new(),
+            catalogue.price_plans,
+        ))
     }
 }
 
-pub fn init() -> AppState {
-    let state = AppState::default();
-    let mut db = state.db.lock().unwrap();
+/// Creates the application state from the runtime configuration
+///
+/// The store holds the accounts and price plans of `catalogue` and is kept in
+/// memory or in the configured log file. When the demo dataset is used with
+/// seeding enabled, smart-meter-1 is given random readings unless it already
+/// has some.
+pub fn init(config: &Config, catalogue: Catalogue) -> Result<AppState, DataStoreError> {
+    let state = match &config.storage {
+        StorageBackend::Memory => AppState::new(InMemoryDataStore::new(
+            catalogue.accounts,
+            HashMap::new(),
+            catalogue.price_plans,
+        )),
+        StorageBackend::File(path) => AppState::new(FileDataStore::open(
+            path,
+            catalogue.accounts,
+            catalogue.price_plans,
+        )?),
+    }
+    .with_limits(config.limits)
+    .with_vat_rates(config.vat_rates);
 
-    db.insert_readings(
-        "smart-meter-1".to_string(),
-        ElectricityReading::generate_random(None, None),
-    );
+    if config.seed && config.catalogue == CatalogueSource::Demo {
+        let smart_meter_id = "smart-meter-1".to_string();
+        let mut data_store = state.db.lock().unwrap();
+        if data_store.get_readings(&smart_meter_id).is_empty() {
+            data_store.insert_readings(
+                smart_meter_id,
+                ElectricityReading::generate_random(None, None),
+            )?;
+        }
+    }
 
-    state.to_owned()
+    Ok(state)
 }
@@ -1,31 +1,296 @@
 use crate::datastore::account::Account;
-use crate::datastore::plan::PricePlan;
+use crate::datastore::error::DataStoreError;
+use crate::datastore::plan::{HalfHourPrice, PricePlan};
 use crate::datastore::reading::ElectricityReading;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+use time::OffsetDateTime;
 
 pub type SmartMeterId = String;
 
+/// What to do when a reading arrives for a meter and timestamp that already has
+/// a different reading
+///
+/// A reading identical to the stored one is always skipped, so retried uploads
+/// never conflict.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Keep the stored reading and skip the new one
+    KeepFirst,
+    /// Replace the stored reading with the new one
+    #[default]
+    KeepLast,
+    /// Reject the whole batch
+    Reject,
+}
+
+/// How many readings of a batch were stored, replaced a stored reading, or were skipped
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InsertSummary {
+    pub inserted: usize,
+    pub replaced: usize,
+    pub skipped: usize,
+}
+
+/// One page of a meter's readings, in chronological order
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReadingsPage {
+    pub readings: Vec<ElectricityReading>,
+    /// Whether further readings follow the last one on this page
+    pub has_more: bool,
+}
+
+/// Storage for accounts, price plans and the readings sent by smart meters
+pub trait DataStore: Send {
+    /// Stores readings for a meter, replacing any stored reading with the same
+    /// timestamp but a different value
+    fn insert_readings(
+        &mut self,
+        smart_meter_id: SmartMeterId,
+        readings: Vec<ElectricityReading>,
+    ) -> Result<InsertSummary, DataStoreError> {
+        self.insert_readings_with_policy(smart_meter_id, readings, ConflictPolicy::default())
+    }
+
+    /// Stores readings for a meter, resolving clashes with stored readings
+    /// according to `policy`
+    ///
+    /// Nothing is stored when `policy` is `Reject` and any reading conflicts, or
+    /// when no account is registered for the meter.
+    fn insert_readings_with_policy(
+        &mut self,
+        smart_meter_id: SmartMeterId,
+        readings: Vec<ElectricityReading>,
+        policy: ConflictPolicy,
+    ) -> Result<InsertSummary, DataStoreError>;
+
+    /// Returns every reading stored for a meter, in chronological order
+    fn get_readings(&self, smart_meter_id: &SmartMeterId) -> Vec<ElectricityReading> {
+        self.get_readings_between(smart_meter_id, None, None)
+    }
+
+    /// Returns the readings for a meter taken at or after `from` and before `to`,
+    /// in chronological order
+    ///
+    /// A missing bound leaves that end of the range open.
+    fn get_readings_between(
+        &self,
+        smart_meter_id: &SmartMeterId,
+        from: Option<OffsetDateTime>,
+        to: Option<OffsetDateTime>,
+    ) -> Vec<ElectricityReading>;
+
+    /// Returns up to `limit` readings for a meter within `from` and `to`, starting
+    /// with the first reading taken strictly after `after`
+    fn get_readings_page(
+        &self,
+        smart_meter_id: &SmartMeterId,
+        from: Option<OffsetDateTime>,
+        to: Option<OffsetDateTime>,
+        after: Option<OffsetDateTime>,
+        limit: usize,
+    ) -> ReadingsPage;
+
+    /// Returns every price plan, including retired ones
+    fn get_price_plans(&self) -> Vec<PricePlan>;
+
+    /// Returns the price plan with the given id, whether or not it is retired
+    fn get_price_plan(&self, price_plan_id: &str) -> Option<PricePlan> {
+        self.get_price_plans()
+            .into_iter()
+            .find(|price_plan| price_plan.supplier_id == price_plan_id)
+    }
+
+    /// Adds a price plan, failing if one with the same id already exists
+    fn add_price_plan(&mut self, price_plan: PricePlan) -> Result<(), DataStoreError>;
+
+    /// Replaces the stored price plan with the same id
+    ///
+    /// Half-hourly prices are managed separately, so the stored plan keeps its own.
+    fn update_price_plan(&mut self, price_plan: PricePlan) -> Result<(), DataStoreError>;
+
+    /// Stores half-hourly prices for a plan, replacing any it has for the same
+    /// half hours
+    fn add_half_hourly_prices(
+        &mut self,
+        price_plan_id: &str,
+        prices: Vec<HalfHourPrice>,
+    ) -> Result<(), DataStoreError>;
+
+    /// Returns every registered account, ordered by smart meter id
+    fn get_accounts(&self) -> BTreeMap<SmartMeterId, Account>;
+
+    fn get_account(&self, smart_meter_id: &SmartMeterId) -> Option<Account>;
+
+    /// Registers an account for a meter, failing if the meter already has one
+    fn register_account(
+        &mut self,
+        smart_meter_id: SmartMeterId,
+        account: Account,
+    ) -> Result<(), DataStoreError>;
+
+    /// Replaces the account registered for a meter
+    fn update_account(
+        &mut self,
+        smart_meter_id: SmartMeterId,
+        account: Account,
+    ) -> Result<(), DataStoreError>;
+
+    /// Removes the account registered for a meter
+    ///
+    /// The meter's readings are kept, and belong to it again if it is registered anew.
+    fn deregister_account(&mut self, smart_meter_id: &SmartMeterId) -> Result<(), DataStoreError>;
+
+    /// Returns the id of the plan a meter's account is on at the given instant
+    fn get_account_supplier_id(
+        &self,
+        smart_meter_id: &SmartMeterId,
+        instant: OffsetDateTime,
+    ) -> Result<String, DataStoreError> {
+        self.get_account(smart_meter_id)
+            .map(|account| account.price_plan_at(instant).to_string())
+            .ok_or_else(|| DataStoreError::UnknownSmartMeter(smart_meter_id.to_string()))
+    }
+}
+
+/// A `DataStore` that keeps everything in memory and is lost on restart
+///
+/// Readings are indexed by time for each meter, so each meter holds at most one
+/// reading per timestamp and its series is always in chronological order.
 #[derive(Debug)]
-pub struct DataStore {
+pub struct InMemoryDataStore {
     accounts: HashMap<SmartMeterId, Account>,
     price_plans: Vec<PricePlan>,
-    readings: HashMap<SmartMeterId, Vec<ElectricityReading>>,
+    readings: HashMap<SmartMeterId, BTreeMap<OffsetDateTime, ElectricityReading>>,
 }
 
-impl DataStore {
+impl InMemoryDataStore {
     pub fn new(
         accounts: HashMap<SmartMeterId, Account>,
         readings: HashMap<SmartMeterId, Vec<ElectricityReading>>,
         price_plans: Vec<PricePlan>,
     ) -> Self {
-        Self {
+        let mut store = Self {
             accounts,
-            readings,
+            readings: HashMap::new(),
             price_plans,
+        };
+        for (smart_meter_id, readings) in readings {
+            store.index_readings(smart_meter_id, readings);
+        }
+        store
+    }
+
+    fn readings_in_range(
+        &self,
+        smart_meter_id: &SmartMeterId,
+        lower: Bound<OffsetDateTime>,
+        to: Option<OffsetDateTime>,
+    ) -> impl Iterator<Item = &ElectricityReading> {
+        let is_empty = match (lower, to) {
+            (Bound::Included(from), Some(to)) => from >= to,
+            (Bound::Excluded(after), Some(to)) => after >= to,
+            _ => false,
+        };
+        let upper = to.map_or(Bound::Unbounded, Bound::Excluded);
+        self.readings
+            .get(smart_meter_id)
+            .filter(|_| !is_empty)
+            .into_iter()
+            .flat_map(move |readings| readings.range((lower, upper)).map(|(_, reading)| reading))
+    }
+
+    /// Works out which readings of a batch need writing under `policy`
+    ///
+    /// # Returns
+    /// The readings to write, each replacing any stored reading at its timestamp,
+    /// and a summary of what writing them will do
+    pub(super) fn resolve_conflicts(
+        &self,
+        smart_meter_id: &SmartMeterId,
+        readings: &[ElectricityReading],
+        policy: ConflictPolicy,
+    ) -> Result<(Vec<ElectricityReading>, InsertSummary), DataStoreError> {
+        if !self.accounts.contains_key(smart_meter_id) {
+            return Err(DataStoreError::UnknownSmartMeter(
+                smart_meter_id.to_string(),
+            ));
+        }
+        let stored = self.readings.get(smart_meter_id);
+        let mut writes = BTreeMap::new();
+        let mut conflicts = Vec::new();
+        for reading in readings {
+            let existing = writes
+                .get(&reading.time)
+                .or_else(|| stored.and_then(|stored| stored.get(&reading.time)));
+            match existing {
+                Some(stored) if stored == reading => {}
+                Some(_) if policy == ConflictPolicy::KeepFirst => {}
+                Some(_) if policy == ConflictPolicy::Reject => conflicts.push(reading.time),
+                _ => {
+                    writes.insert(reading.time, reading.clone());
+                }
+            }
+        }
+        if !conflicts.is_empty() {
+            return Err(DataStoreError::ConflictingReadings(conflicts));
+        }
+
+        let replaced = writes
+            .keys()
+            .filter(|time| stored.is_some_and(|stored| stored.contains_key(time)))
+            .count();
+        let summary = InsertSummary {
+            inserted: writes.len() - replaced,
+            replaced,
+            skipped: readings.len() - writes.len(),
+        };
+        Ok((writes.into_values().collect(), summary))
+    }
+
+    /// Adds a price plan, or replaces the stored one with the same id while
+    /// keeping its half-hourly prices
+    pub(super) fn save_price_plan(&mut self, mut price_plan: PricePlan) {
+        match self.price_plan_index(&price_plan.supplier_id) {
+            Some(index) => {
+                let stored = &mut self.price_plans[index];
+                price_plan.half_hourly_prices = std::mem::take(&mut stored.half_hourly_prices);
+                *stored = price_plan;
+            }
+            None => self.price_plans.push(price_plan),
         }
     }
 
-    pub fn insert_readings(
+    pub(super) fn save_half_hourly_prices(
+        &mut self,
+        price_plan_id: &str,
+        prices: Vec<HalfHourPrice>,
+    ) -> Result<(), DataStoreError> {
+        let index = self
+            .price_plan_index(price_plan_id)
+            .ok_or_else(|| DataStoreError::UnknownPricePlan(price_plan_id.to_string()))?;
+        self.price_plans[index].add_half_hourly_prices(prices);
+        Ok(())
+    }
+
+    /// Registers an account for a meter, or replaces the one it has
+    pub(super) fn save_account(&mut self, smart_meter_id: SmartMeterId, account: Account) {
+        self.accounts.insert(smart_meter_id, account);
+    }
+
+    pub(super) fn remove_account(&mut self, smart_meter_id: &SmartMeterId) {
+        self.accounts.remove(smart_meter_id);
+    }
+
+    fn price_plan_index(&self, price_plan_id: &str) -> Option<usize> {
+        self.price_plans
+            .iter()
+            .position(|price_plan| price_plan.supplier_id == price_plan_id)
+    }
+
+    pub(super) fn index_readings(
         &mut self,
         smart_meter_id: SmartMeterId,
         readings: Vec<ElectricityReading>,
@@ -33,26 +298,129 @@ impl DataStore {
         self.readings
             .entry(smart_meter_id)
             .or_default()
-            .extend(readings);
+            .extend(readings.into_iter().map(|r| (r.time, r)));
     }
+}
 
-    pub fn get_readings(&self, smart_meter_id: &SmartMeterId) -> Vec<ElectricityReading> {
-        self.readings
-            .get(smart_meter_id)
+impl DataStore for InMemoryDataStore {
+    fn insert_readings_with_policy(
+        &mut self,
+        smart_meter_id: SmartMeterId,
+        readings: Vec<ElectricityReading>,
+        policy: ConflictPolicy,
+    ) -> Result<InsertSummary, DataStoreError> {
+        let (writes, summary) = self.resolve_conflicts(&smart_meter_id, &readings, policy)?;
+        self.index_readings(smart_meter_id, writes);
+        Ok(summary)
+    }
+
+    fn get_readings_between(
+        &self,
+        smart_meter_id: &SmartMeterId,
+        from: Option<OffsetDateTime>,
+        to: Option<OffsetDateTime>,
+    ) -> Vec<ElectricityReading> {
+        let lower = from.map_or(Bound::Unbounded, Bound::Included);
+        self.readings_in_range(smart_meter_id, lower, to)
             .cloned()
-            .unwrap_or_default()
+            .collect()
+    }
+
+    fn get_readings_page(
+        &self,
+        smart_meter_id: &SmartMeterId,
+        from: Option<OffsetDateTime>,
+        to: Option<OffsetDateTime>,
+        after: Option<OffsetDateTime>,
+        limit: usize,
+    ) -> ReadingsPage {
+        let lower = match (from, after) {
+            (Some(from), Some(after)) if from > after => Bound::Included(from),
+            (_, Some(after)) => Bound::Excluded(after),
+            (Some(from), None) => Bound::Included(from),
+            (None, None) => Bound::Unbounded,
+        };
+        let mut readings = self
+            .readings_in_range(smart_meter_id, lower, to)
+            .take(limit.saturating_add(1))
+            .cloned()
+            .collect::<Vec<ElectricityReading>>();
+
+        let has_more = readings.len() > limit;
+        readings.truncate(limit);
+        ReadingsPage { readings, has_more }
     }
 
-    pub fn get_price_plans(&self) -> Vec<PricePlan> {
+    fn get_price_plans(&self) -> Vec<PricePlan> {
         self.price_plans.clone()
     }
 
-    pub fn get_account_supplier_id(&self, smart_meter_id: &SmartMeterId) -> String {
+    fn add_price_plan(&mut self, price_plan: PricePlan) -> Result<(), DataStoreError> {
+        if self.price_plan_index(&price_plan.supplier_id).is_some() {
+            return Err(DataStoreError::DuplicatePricePlan(price_plan.supplier_id));
+        }
+        self.price_plans.push(price_plan);
+        Ok(())
+    }
+
+    fn update_price_plan(&mut self, price_plan: PricePlan) -> Result<(), DataStoreError> {
+        if self.price_plan_index(&price_plan.supplier_id).is_none() {
+            return Err(DataStoreError::UnknownPricePlan(price_plan.supplier_id));
+        }
+        self.save_price_plan(price_plan);
+        Ok(())
+    }
+
+    fn add_half_hourly_prices(
+        &mut self,
+        price_plan_id: &str,
+        prices: Vec<HalfHourPrice>,
+    ) -> Result<(), DataStoreError> {
+        self.save_half_hourly_prices(price_plan_id, prices)
+    }
+
+    fn get_accounts(&self) -> BTreeMap<SmartMeterId, Account> {
         self.accounts
-            .get(smart_meter_id)
-            .unwrap()
-            .price_plan_id
-            .to_string()
+            .iter()
+            .map(|(smart_meter_id, account)| (smart_meter_id.clone(), account.clone()))
+            .collect()
+    }
+
+    fn get_account(&self, smart_meter_id: &SmartMeterId) -> Option<Account> {
+        self.accounts.get(smart_meter_id).cloned()
+    }
+
+    fn register_account(
+        &mut self,
+        smart_meter_id: SmartMeterId,
+        account: Account,
+    ) -> Result<(), DataStoreError> {
+        if self.accounts.contains_key(&smart_meter_id) {
+            return Err(DataStoreError::DuplicateSmartMeter(smart_meter_id));
+        }
+        self.accounts.insert(smart_meter_id, account);
+        Ok(())
+    }
+
+    fn update_account(
+        &mut self,
+        smart_meter_id: SmartMeterId,
+        account: Account,
+    ) -> Result<(), DataStoreError> {
+        match self.accounts.get_mut(&smart_meter_id) {
+            Some(stored) => {
+                *stored = account;
+                Ok(())
+            }
+            None => Err(DataStoreError::UnknownSmartMeter(smart_meter_id)),
+        }
+    }
+
+    fn deregister_account(&mut self, smart_meter_id: &SmartMeterId) -> Result<(), DataStoreError> {
+        self.accounts
+            .remove(smart_meter_id)
+            .map(|_| ())
+            .ok_or_else(|| DataStoreError::UnknownSmartMeter(smart_meter_id.to_string()))
     }
 }
 
@@ -62,32 +430,18 @@ mod tests {
     use time::OffsetDateTime;
 
     fn create_test_reading(time: i64, reading: f64) -> ElectricityReading {
-        ElectricityReading {
-            time: OffsetDateTime::from_unix_timestamp(time).unwrap(),
-            reading,
-        }
+        ElectricityReading::new(OffsetDateTime::from_unix_timestamp(time).unwrap(), reading)
     }
 
-    fn setup_test_store() -> DataStore {
+    fn setup_test_store() -> InMemoryDataStore {
         let mut accounts = HashMap::new();
-        accounts.insert(
-            "meter-1".to_string(),
-            Account {
-                price_plan_id: "plan-1".to_string(),
-                user: "user-1".to_string(),
-            },
-        );
+        accounts.insert("meter-1".to_string(), Account::new("plan-1", "user-1"));
 
-        let price_plans = vec![PricePlan {
-            supplier_id: "plan-1".to_string(),
-            plan_name: "plan-1".to_string(),
-            rate_multipliers: HashMap::new(),
-            unit_rate: 10.0,
-        }];
+        let price_plans = vec![PricePlan::new("plan-1", "plan-1", 10.0, HashMap::new())];
 
         let readings = HashMap::new();
 
-        DataStore::new(accounts, readings, price_plans)
+        InMemoryDataStore::new(accounts, readings, price_plans)
     }
 
     #[test]
@@ -98,7 +452,9 @@ mod tests {
             create_test_reading(2000, 2.5),
         ];
 
-        store.insert_readings("meter-1".to_string(), readings.clone());
+        store
+            .insert_readings("meter-1".to_string(), readings.clone())
+            .unwrap();
 
         assert_eq!(store.get_readings(&"meter-1".to_string()), readings);
     }
@@ -107,7 +463,9 @@ mod tests {
     fn test_get_readings_existing_meter() {
         let mut store = setup_test_store();
         let readings = vec![create_test_reading(1000, 1.5)];
-        store.insert_readings("meter-1".to_string(), readings.clone());
+        store
+            .insert_readings("meter-1".to_string(), readings.clone())
+            .unwrap();
 
         assert_eq!(store.get_readings(&"meter-1".to_string()), readings);
     }
@@ -119,6 +477,216 @@ mod tests {
         assert!(store.get_readings(&"nonexistent".to_string()).is_empty());
     }
 
+    #[test]
+    fn test_get_readings_in_chronological_order() {
+        let mut store = setup_test_store();
+        store
+            .insert_readings(
+                "meter-1".to_string(),
+                vec![
+                    create_test_reading(3000, 3.5),
+                    create_test_reading(1000, 1.5),
+                ],
+            )
+            .unwrap();
+        store
+            .insert_readings("meter-1".to_string(), vec![create_test_reading(2000, 2.5)])
+            .unwrap();
+
+        assert_eq!(
+            store.get_readings(&"meter-1".to_string()),
+            vec![
+                create_test_reading(1000, 1.5),
+                create_test_reading(2000, 2.5),
+                create_test_reading(3000, 3.5),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_readings_between() {
+        let mut store = setup_test_store();
+        let readings = vec![
+            create_test_reading(1000, 1.5),
+            create_test_reading(2000, 2.5),
+            create_test_reading(3000, 3.5),
+        ];
+        store
+            .insert_readings("meter-1".to_string(), readings)
+            .unwrap();
+        let time = |t| Some(OffsetDateTime::from_unix_timestamp(t).unwrap());
+
+        assert_eq!(
+            store.get_readings_between(&"meter-1".to_string(), time(2000), time(3000)),
+            vec![create_test_reading(2000, 2.5)]
+        );
+        assert_eq!(
+            store.get_readings_between(&"meter-1".to_string(), time(1500), None),
+            vec![
+                create_test_reading(2000, 2.5),
+                create_test_reading(3000, 3.5)
+            ]
+        );
+        assert!(store
+            .get_readings_between(&"meter-1".to_string(), time(3000), time(1000))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_get_readings_page() {
+        let mut store = setup_test_store();
+        let readings = vec![
+            create_test_reading(1000, 1.5),
+            create_test_reading(2000, 2.5),
+            create_test_reading(3000, 3.5),
+        ];
+        store
+            .insert_readings("meter-1".to_string(), readings)
+            .unwrap();
+
+        let first_page = store.get_readings_page(&"meter-1".to_string(), None, None, None, 2);
+        assert_eq!(
+            first_page,
+            ReadingsPage {
+                readings: vec![
+                    create_test_reading(1000, 1.5),
+                    create_test_reading(2000, 2.5)
+                ],
+                has_more: true,
+            }
+        );
+
+        let after = first_page.readings.last().map(|r| r.time);
+        let second_page = store.get_readings_page(&"meter-1".to_string(), None, None, after, 2);
+        assert_eq!(
+            second_page,
+            ReadingsPage {
+                readings: vec![create_test_reading(3000, 3.5)],
+                has_more: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_retried_readings_are_skipped() {
+        let mut store = setup_test_store();
+        let readings = vec![
+            create_test_reading(1000, 1.5),
+            create_test_reading(2000, 2.5),
+        ];
+        store
+            .insert_readings("meter-1".to_string(), readings.clone())
+            .unwrap();
+
+        let summary = store
+            .insert_readings("meter-1".to_string(), readings.clone())
+            .unwrap();
+
+        assert_eq!(
+            summary,
+            InsertSummary {
+                inserted: 0,
+                replaced: 0,
+                skipped: 2
+            }
+        );
+        assert_eq!(store.get_readings(&"meter-1".to_string()), readings);
+    }
+
+    #[test]
+    fn test_conflict_policies() {
+        let initial = vec![create_test_reading(1000, 1.5)];
+        let batch = vec![
+            create_test_reading(1000, 9.9),
+            create_test_reading(2000, 2.5),
+        ];
+
+        let mut store = setup_test_store();
+        store
+            .insert_readings("meter-1".to_string(), initial.clone())
+            .unwrap();
+        let summary = store
+            .insert_readings_with_policy(
+                "meter-1".to_string(),
+                batch.clone(),
+                ConflictPolicy::KeepFirst,
+            )
+            .unwrap();
+        assert_eq!(
+            summary,
+            InsertSummary {
+                inserted: 1,
+                replaced: 0,
+                skipped: 1
+            }
+        );
+        assert_eq!(
+            store.get_readings(&"meter-1".to_string())[0],
+            create_test_reading(1000, 1.5)
+        );
+
+        let mut store = setup_test_store();
+        store
+            .insert_readings("meter-1".to_string(), initial.clone())
+            .unwrap();
+        let summary = store
+            .insert_readings_with_policy(
+                "meter-1".to_string(),
+                batch.clone(),
+                ConflictPolicy::KeepLast,
+            )
+            .unwrap();
+        assert_eq!(
+            summary,
+            InsertSummary {
+                inserted: 1,
+                replaced: 1,
+                skipped: 0
+            }
+        );
+        assert_eq!(
+            store.get_readings(&"meter-1".to_string())[0],
+            create_test_reading(1000, 9.9)
+        );
+
+        let mut store = setup_test_store();
+        store
+            .insert_readings("meter-1".to_string(), initial.clone())
+            .unwrap();
+        let result =
+            store.insert_readings_with_policy("meter-1".to_string(), batch, ConflictPolicy::Reject);
+        assert!(matches!(
+            result,
+            Err(DataStoreError::ConflictingReadings(times)) if times == vec![initial[0].time]
+        ));
+        assert_eq!(store.get_readings(&"meter-1".to_string()), initial);
+    }
+
+    #[test]
+    fn test_duplicates_within_a_batch() {
+        let mut store = setup_test_store();
+        let batch = vec![
+            create_test_reading(1000, 1.5),
+            create_test_reading(1000, 1.5),
+            create_test_reading(1000, 2.5),
+        ];
+
+        let summary = store.insert_readings("meter-1".to_string(), batch).unwrap();
+
+        assert_eq!(
+            summary,
+            InsertSummary {
+                inserted: 1,
+                replaced: 0,
+                skipped: 2
+            }
+        );
+        assert_eq!(
+            store.get_readings(&"meter-1".to_string()),
+            vec![create_test_reading(1000, 2.5)]
+        );
+    }
+
     #[test]
     fn test_get_price_plans() {
         let store = setup_test_store();
@@ -128,20 +696,91 @@ mod tests {
         assert_eq!(plans[0].unit_rate, 10.0);
     }
 
+    #[test]
+    fn test_readings_for_unregistered_meter_are_rejected() {
+        let mut store = setup_test_store();
+
+        let result = store.insert_readings(
+            "unregistered".to_string(),
+            vec![create_test_reading(1606636800, 1.0)],
+        );
+
+        assert!(matches!(result, Err(DataStoreError::UnknownSmartMeter(_))));
+        assert!(store.get_readings(&"unregistered".to_string()).is_empty());
+    }
+
+    #[test]
+    fn test_register_and_deregister_account() {
+        let mut store = setup_test_store();
+        let meter = "meter-2".to_string();
+
+        store
+            .register_account(meter.clone(), Account::new("plan-1", "user-2"))
+            .unwrap();
+        assert!(matches!(
+            store.register_account(meter.clone(), Account::new("plan-1", "user-2")),
+            Err(DataStoreError::DuplicateSmartMeter(_))
+        ));
+        store.deregister_account(&meter).unwrap();
+
+        assert_eq!(store.get_account(&meter), None);
+        assert!(matches!(
+            store.deregister_account(&meter),
+            Err(DataStoreError::UnknownSmartMeter(_))
+        ));
+    }
+
+    #[test]
+    fn test_add_price_plan() {
+        let mut store = setup_test_store();
+
+        store
+            .add_price_plan(PricePlan::new("plan-2", "plan-2", 5.0, HashMap::new()))
+            .unwrap();
+
+        assert_eq!(store.get_price_plan("plan-2").unwrap().unit_rate, 5.0);
+        assert!(matches!(
+            store.add_price_plan(PricePlan::new("plan-2", "plan-2", 1.0, HashMap::new())),
+            Err(DataStoreError::DuplicatePricePlan(id)) if id == "plan-2"
+        ));
+    }
+
+    #[test]
+    fn test_update_price_plan() {
+        let mut store = setup_test_store();
+
+        store
+            .update_price_plan(PricePlan::new("plan-1", "renamed", 12.0, HashMap::new()))
+            .unwrap();
+
+        let plans = store.get_price_plans();
+        assert_eq!(plans.len(), 1);
+        assert_eq!(plans[0].plan_name, "renamed");
+        assert!(matches!(
+            store.update_price_plan(PricePlan::new("plan-9", "plan-9", 1.0, HashMap::new())),
+            Err(DataStoreError::UnknownPricePlan(id)) if id == "plan-9"
+        ));
+    }
+
     #[test]
     fn test_get_account_supplier_id() {
         let store = setup_test_store();
 
         assert_eq!(
-            store.get_account_supplier_id(&"meter-1".to_string()),
+            store
+                .get_account_supplier_id(&"meter-1".to_string(), OffsetDateTime::now_utc())
+                .unwrap(),
             "plan-1"
         );
     }
 
     #[test]
-    #[should_panic]
     fn test_get_account_supplier_id_nonexistent() {
         let store = setup_test_store();
-        store.get_account_supplier_id(&"nonexistent".to_string());
+
+        assert!(matches!(
+            store.get_account_supplier_id(&"nonexistent".to_string(), OffsetDateTime::now_utc()),
+            Err(DataStoreError::UnknownSmartMeter(id)) if id == "nonexistent"
+        ));
     }
 }
@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+/// Which VAT rate a price plan's charges are taxed at
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VatCategory {
+    /// Energy supplied to homes, taxed at the reduced rate
+    #[default]
+    Domestic,
+    /// Everything else, taxed at the standard rate
+    Standard,
+}
+
+/// The VAT rates charges are taxed at, as fractions such as 0.2 for 20%
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VatRates {
+    pub standard: f64,
+    /// The reduced rate for domestic energy
+    pub domestic: f64,
+}
+
+impl VatRates {
+    pub fn rate_for(&self, category: VatCategory) -> f64 {
+        match category {
+            VatCategory::Domestic => self.domestic,
+            VatCategory::Standard => self.standard,
+        }
+    }
+}
+
+impl Default for VatRates {
+    fn default() -> Self {
+        Self {
+            standard: 0.2,
+            domestic: 0.05,
+        }
+    }
+}
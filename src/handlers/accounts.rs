@@ -0,0 +1,356 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+
+use crate::datastore::account::Account;
+use crate::datastore::error::DataStoreError;
+use crate::datastore::state::AppState;
+use crate::datastore::store::{DataStore, SmartMeterId};
+use crate::handlers::error::ApiError;
+use crate::handlers::extract::{Json, Path};
+use crate::models::accounts::{
+    AccountResponse, CreateAccountRequest, SwitchPricePlanRequest, UpdateAccountRequest,
+};
+use crate::models::error::FieldError;
+use time::OffsetDateTime;
+
+/// Lists every registered account, ordered by smart meter id
+pub async fn list_accounts(State(state): State<AppState>) -> Json<Vec<AccountResponse>> {
+    let now = OffsetDateTime::now_utc();
+    let data_store = state.db.lock().unwrap();
+    let accounts = data_store
+        .get_accounts()
+        .iter()
+        .map(|(smart_meter_id, account)| AccountResponse::new(smart_meter_id, account, now))
+        .collect();
+    Json(accounts)
+}
+
+/// Returns the account registered for a meter
+///
+/// Responds with 404 Not Found when the meter has no account.
+pub async fn get_account(
+    Path(smart_meter_id): Path<SmartMeterId>,
+    State(state): State<AppState>,
+) -> Result<Json<AccountResponse>, ApiError> {
+    let data_store = state.db.lock().unwrap();
+    let account = find_account(&*data_store, &smart_meter_id)?;
+    Ok(Json(AccountResponse::new(
+        &smart_meter_id,
+        &account,
+        OffsetDateTime::now_utc(),
+    )))
+}
+
+/// Registers a smart meter, attaching it to a user and a price plan
+///
+/// Responds with 201 Created, with 409 Conflict when the meter is already
+/// registered, or with 422 Unprocessable Entity when a field is invalid or the
+/// price plan does not exist or is retired.
+pub async fn register_account(
+    State(state): State<AppState>,
+    Json(body): Json<CreateAccountRequest>,
+) -> Result<(StatusCode, Json<AccountResponse>), ApiError> {
+    let mut data_store = state.db.lock().unwrap();
+    let mut errors = Vec::new();
+    if body.smart_meter_id.trim().is_empty() {
+        errors.push(FieldError::new("smart_meter_id", "must not be empty"));
+    }
+    errors.extend(check_user(&body.user));
+    errors.extend(check_price_plan(&*data_store, &body.price_plan_id));
+    if !errors.is_empty() {
+        return Err(invalid_account(errors));
+    }
+
+    let account = Account::new(&body.price_plan_id, &body.user);
+    let response = AccountResponse::new(&body.smart_meter_id, &account, OffsetDateTime::now_utc());
+    data_store.register_account(body.smart_meter_id, account)?;
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// Changes the details of a registered meter's account
+///
+/// Responds with 404 Not Found when the meter has no account.
+pub async fn update_account(
+    Path(smart_meter_id): Path<SmartMeterId>,
+    State(state): State<AppState>,
+    Json(body): Json<UpdateAccountRequest>,
+) -> Result<Json<AccountResponse>, ApiError> {
+    let mut data_store = state.db.lock().unwrap();
+    let mut account = find_account(&*data_store, &smart_meter_id)?;
+    if let Some(error) = check_user(&body.user) {
+        return Err(invalid_account(vec![error]));
+    }
+
+    account.user = body.user;
+    let response = AccountResponse::new(&smart_meter_id, &account, OffsetDateTime::now_utc());
+    data_store.update_account(smart_meter_id, account)?;
+    Ok(Json(response))
+}
+
+/// Switches an account to another price plan from a given time, keeping the
+/// plans it was on before so earlier readings are still billed against them
+///
+/// Switches are recorded in order, so each must take effect after the previous
+/// one. Responds with 201 Created, with 404 Not Found when the meter has no
+/// account, or with 422 Unprocessable Entity when the switch is not allowed.
+pub async fn switch_price_plan(
+    Path(smart_meter_id): Path<SmartMeterId>,
+    State(state): State<AppState>,
+    Json(body): Json<SwitchPricePlanRequest>,
+) -> Result<(StatusCode, Json<AccountResponse>), ApiError> {
+    let mut data_store = state.db.lock().unwrap();
+    let mut account = find_account(&*data_store, &smart_meter_id)?;
+    let effective_from = body.effective_from.unwrap_or_else(OffsetDateTime::now_utc);
+
+    let mut errors = Vec::new();
+    errors.extend(check_price_plan(&*data_store, &body.price_plan_id));
+    if account
+        .last_switch()
+        .is_some_and(|last_switch| effective_from <= last_switch)
+    {
+        errors.push(FieldError::new(
+            "effective_from",
+            "must be later than the previous switch",
+        ));
+    } else if account.price_plan_at(effective_from) == body.price_plan_id {
+        errors.push(FieldError::new(
+            "price_plan_id",
+            "is already the plan in force at that time",
+        ));
+    }
+    if !errors.is_empty() {
+        return Err(invalid_account(errors));
+    }
+
+    account.switch_price_plan(&body.price_plan_id, effective_from);
+    let response = AccountResponse::new(&smart_meter_id, &account, OffsetDateTime::now_utc());
+    data_store.update_account(smart_meter_id, account)?;
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// Deregisters a meter, after which readings sent for it are rejected
+///
+/// Responds with 204 No Content, or 404 Not Found when the meter has no account.
+pub async fn deregister_account(
+    Path(smart_meter_id): Path<SmartMeterId>,
+    State(state): State<AppState>,
+) -> Result<StatusCode, ApiError> {
+    let mut data_store = state.db.lock().unwrap();
+    data_store.deregister_account(&smart_meter_id)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn find_account(
+    data_store: &dyn DataStore,
+    smart_meter_id: &SmartMeterId,
+) -> Result<Account, ApiError> {
+    data_store
+        .get_account(smart_meter_id)
+        .ok_or_else(|| DataStoreError::UnknownSmartMeter(smart_meter_id.clone()).into())
+}
+
+fn check_user(user: &str) -> Option<FieldError> {
+    user.trim()
+        .is_empty()
+        .then(|| FieldError::new("user", "must not be empty"))
+}
+
+/// Checks that a plan can be taken up, i.e. that it exists and is not retired
+fn check_price_plan(data_store: &dyn DataStore, price_plan_id: &str) -> Option<FieldError> {
+    match data_store.get_price_plan(price_plan_id) {
+        None => Some(FieldError::new(
+            "price_plan_id",
+            "does not match any price plan",
+        )),
+        Some(price_plan) if price_plan.retired => {
+            Some(FieldError::new("price_plan_id", "is a retired price plan"))
+        }
+        Some(_) => None,
+    }
+}
+
+fn invalid_account(errors: Vec<FieldError>) -> ApiError {
+    ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "invalid-account",
+        "the account is not valid",
+    )
+    .with_errors(errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::extract::State;
+    use axum::http::StatusCode;
+
+    use time::macros::datetime;
+
+    use crate::datastore::account::PlanAssignment;
+    use crate::datastore::state::AppState;
+    use crate::handlers::accounts::{
+        deregister_account, register_account, switch_price_plan, update_account,
+    };
+    use crate::handlers::extract::{Json, Path};
+    use crate::handlers::plans::retire_price_plan;
+    use crate::models::accounts::{
+        AccountResponse, CreateAccountRequest, SwitchPricePlanRequest, UpdateAccountRequest,
+    };
+    use crate::models::error::FieldError;
+
+    fn make_state() -> AppState {
+        AppState::default()
+    }
+
+    fn request(smart_meter_id: &str, price_plan_id: &str) -> Json<CreateAccountRequest> {
+        Json(CreateAccountRequest {
+            smart_meter_id: smart_meter_id.to_string(),
+            user: "Jo".to_string(),
+            price_plan_id: price_plan_id.to_string(),
+        })
+    }
+
+    #[tokio::test]
+    async fn testing_registering_an_account() {
+        let state = make_state();
+
+        let (status, Json(result)) = register_account(
+            State(state.clone()),
+            request("smart-meter-5", "price-plan-1"),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(
+            result,
+            AccountResponse {
+                smart_meter_id: "smart-meter-5".to_string(),
+                user: "Jo".to_string(),
+                price_plan_id: "price-plan-1".to_string(),
+                price_plan_history: vec![PlanAssignment {
+                    price_plan_id: "price-plan-1".to_string(),
+                    effective_from: None,
+                }],
+            }
+        );
+        let error = register_account(State(state), request("smart-meter-5", "price-plan-1"))
+            .await
+            .unwrap_err();
+        assert_eq!(error.status, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn testing_registering_onto_unknown_or_retired_plan() {
+        let state = make_state();
+        retire_price_plan(Path("price-plan-2".to_string()), State(state.clone()))
+            .await
+            .unwrap();
+
+        let unknown = register_account(State(state.clone()), request("smart-meter-5", "nope"))
+            .await
+            .unwrap_err();
+        let retired = register_account(State(state), request("smart-meter-5", "price-plan-2"))
+            .await
+            .unwrap_err();
+
+        assert_eq!(unknown.status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            retired.errors,
+            vec![FieldError::new("price_plan_id", "is a retired price plan")]
+        );
+    }
+
+    #[tokio::test]
+    async fn testing_updating_an_account_on_a_retired_plan() {
+        let state = make_state();
+        retire_price_plan(Path("price-plan-2".to_string()), State(state.clone()))
+            .await
+            .unwrap();
+
+        let Json(result) = update_account(
+            Path("smart-meter-3".to_string()),
+            State(state),
+            Json(UpdateAccountRequest {
+                user: "Andrea Smith".to_string(),
+            }),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.user, "Andrea Smith");
+        assert_eq!(result.price_plan_id, "price-plan-2");
+    }
+
+    #[tokio::test]
+    async fn testing_switching_price_plan() {
+        let state = make_state();
+        let switch = |price_plan_id: &str, effective_from| {
+            Json(SwitchPricePlanRequest {
+                price_plan_id: price_plan_id.to_string(),
+                effective_from: Some(effective_from),
+            })
+        };
+        let path = || Path("smart-meter-0".to_string());
+
+        let (status, Json(result)) = switch_price_plan(
+            path(),
+            State(state.clone()),
+            switch("price-plan-2", datetime!(2020-11-29 09:00:00 UTC)),
+        )
+        .await
+        .unwrap();
+        let earlier = switch_price_plan(
+            path(),
+            State(state.clone()),
+            switch("price-plan-1", datetime!(2020-11-29 08:00:00 UTC)),
+        )
+        .await
+        .unwrap_err();
+        let same = switch_price_plan(
+            path(),
+            State(state),
+            switch("price-plan-2", datetime!(2020-11-29 10:00:00 UTC)),
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(result.price_plan_id, "price-plan-2");
+        assert_eq!(
+            result.price_plan_history,
+            vec![
+                PlanAssignment {
+                    price_plan_id: "price-plan-0".to_string(),
+                    effective_from: None,
+                },
+                PlanAssignment {
+                    price_plan_id: "price-plan-2".to_string(),
+                    effective_from: Some(datetime!(2020-11-29 09:00:00 UTC)),
+                },
+            ]
+        );
+        assert_eq!(
+            earlier.errors,
+            vec![FieldError::new(
+                "effective_from",
+                "must be later than the previous switch"
+            )]
+        );
+        assert_eq!(same.status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn testing_deregistering_an_account() {
+        let state = make_state();
+
+        let status = deregister_account(Path("smart-meter-0".to_string()), State(state.clone()))
+            .await
+            .unwrap();
+        let error = deregister_account(Path("smart-meter-0".to_string()), State(state))
+            .await
+            .unwrap_err();
+
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert_eq!(error.status, StatusCode::NOT_FOUND);
+    }
+}
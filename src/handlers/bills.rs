@@ -0,0 +1,228 @@
+use crate::datastore::account::Account;
+use crate::datastore::bill::{Bill, BillingPeriod};
+use crate::datastore::consumption::{Consumption, IntegrationMethod};
+use crate::datastore::error::DataStoreError;
+use crate::datastore::money::Rounding;
+use crate::datastore::plan::PricePlan;
+use crate::datastore::state::AppState;
+use crate::handlers::error::ApiError;
+use crate::handlers::extract::{Json, Path, Query};
+use crate::invoice::{html, pdf, Invoice};
+use crate::models::bills::{
+    GetBillQueryParams, GetBillResponse, GetInvoiceQueryParams, InvoiceFormat,
+};
+use axum::extract::State;
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+
+/// Produces an itemised bill for a meter's consumption over a calendar month
+///
+/// The consumption is billed on the plans the account was on at the time,
+/// with a line for each rate band used, the standing charge and any export
+/// credit, followed by the VAT and totals.
+///
+/// Responds with 404 Not Found when no account is registered for the meter, or
+/// with 422 Unprocessable Entity when the account's plans during the month are
+/// in different currencies.
+pub async fn get_bill(
+    Path(smart_meter_id): Path<String>,
+    Query(query): Query<GetBillQueryParams>,
+    State(state): State<AppState>,
+) -> Result<Json<GetBillResponse>, ApiError> {
+    let billed = generate_bill(
+        &state,
+        &smart_meter_id,
+        query.period,
+        query.method,
+        query.rounding,
+    )?;
+
+    Ok(Json(GetBillResponse {
+        smart_meter_id,
+        bill: billed.bill,
+    }))
+}
+
+/// Renders a meter's bill for a calendar month as a customer-facing invoice
+///
+/// The invoice shows the account holder, the meter, the bill's lines and
+/// totals, the energy used each day and the rates of the plans billed. It is
+/// an HTML page unless `format` is `pdf`.
+///
+/// Responds like [`get_bill`] when the bill cannot be produced.
+pub async fn get_invoice(
+    Path(smart_meter_id): Path<String>,
+    Query(query): Query<GetInvoiceQueryParams>,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    let billed = generate_bill(
+        &state,
+        &smart_meter_id,
+        query.period,
+        query.method,
+        query.rounding,
+    )?;
+    let invoice = Invoice::new(
+        &smart_meter_id,
+        &billed.account,
+        billed.bill,
+        &billed.consumption,
+        &billed.price_plans,
+    );
+
+    let response = match query.format {
+        InvoiceFormat::Html => (
+            [(CONTENT_TYPE, "text/html; charset=utf-8".to_string())],
+            html::render(&invoice),
+        )
+            .into_response(),
+        InvoiceFormat::Pdf => (
+            [
+                (CONTENT_TYPE, "application/pdf".to_string()),
+                (
+                    CONTENT_DISPOSITION,
+                    format!(
+                        "inline; filename=\"invoice-{smart_meter_id}-{}.pdf\"",
+                        query.period
+                    ),
+                ),
+            ],
+            pdf::render(&invoice),
+        )
+            .into_response(),
+    };
+    Ok(response)
+}
+
+/// A bill with what it was generated from
+struct Billed {
+    account: Account,
+    consumption: Consumption,
+    price_plans: Vec<PricePlan>,
+    bill: Bill,
+}
+
+fn generate_bill(
+    state: &AppState,
+    smart_meter_id: &str,
+    period: BillingPeriod,
+    method: IntegrationMethod,
+    rounding: Rounding,
+) -> Result<Billed, ApiError> {
+    let data_store = state.db.lock().unwrap();
+    let smart_meter_id = smart_meter_id.to_string();
+    let account = data_store
+        .get_account(&smart_meter_id)
+        .ok_or_else(|| DataStoreError::UnknownSmartMeter(smart_meter_id.clone()))?;
+
+    let stored_readings = data_store.get_readings(&smart_meter_id);
+    let consumption = Consumption::from_readings(&stored_readings, method);
+    let price_plans = data_store.get_price_plans();
+    let bill = Bill::generate(
+        &account,
+        &consumption,
+        &price_plans,
+        period,
+        rounding,
+        &state.vat_rates,
+    )
+    .ok_or_else(|| {
+        ApiError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "mixed-currencies",
+            format!(
+                "the plans smart meter {smart_meter_id} was on in {period} are in different currencies"
+            ),
+        )
+    })?;
+
+    Ok(Billed {
+        account,
+        consumption,
+        price_plans,
+        bill,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::datastore::bill::{BillingPeriod, LineKind};
+    use crate::datastore::consumption::IntegrationMethod;
+    use crate::datastore::error::DataStoreError;
+    use crate::datastore::money::{Currency, Money, Rounding};
+    use crate::datastore::reading::ElectricityReading;
+    use crate::datastore::state::AppState;
+    use crate::handlers::bills::get_bill;
+    use crate::handlers::error::ApiError;
+    use crate::handlers::extract::{Path, Query};
+    use crate::models::bills::GetBillQueryParams;
+    use axum::extract::State;
+    use time::macros::datetime;
+    use time::Month;
+
+    fn gbp(amount: f64) -> Money {
+        Money::from_f64(amount, Currency::Gbp)
+    }
+
+    fn query(month: Month) -> Query<GetBillQueryParams> {
+        Query(GetBillQueryParams {
+            period: BillingPeriod::new(2020, month),
+            method: IntegrationMethod::Trapezoidal,
+            rounding: Rounding::PerLine,
+        })
+    }
+
+    #[tokio::test]
+    async fn testing_getting_a_bill() {
+        let state = AppState::default();
+        {
+            let mut db = state.db.lock().unwrap();
+            let readings = vec![
+                ElectricityReading::new(datetime!(2020-11-29 08:00:00 UTC), 1.0),
+                ElectricityReading::new(datetime!(2020-11-29 09:00:00 UTC), 1.0),
+            ];
+            db.insert_readings("smart-meter-0".to_string(), readings)
+                .unwrap();
+        }
+
+        let path = Path("smart-meter-0".to_string());
+        let bill = get_bill(path, query(Month::November), State(state.clone()))
+            .await
+            .unwrap()
+            .0
+            .bill;
+
+        assert_eq!(bill.consumption_kwh, 1.0);
+        assert_eq!(bill.lines[0].kind, LineKind::Consumption);
+        assert_eq!(bill.lines[0].amount, gbp(10.0));
+        assert_eq!(bill.subtotal, gbp(10.0));
+        assert_eq!(bill.tax, gbp(0.5));
+        assert_eq!(bill.total, gbp(10.5));
+
+        let path = Path("smart-meter-0".to_string());
+        let december = get_bill(path, query(Month::December), State(state))
+            .await
+            .unwrap()
+            .0
+            .bill;
+
+        assert_eq!(december.lines.len(), 1);
+        assert_eq!(december.lines[0].kind, LineKind::StandingCharge);
+        assert_eq!(december.lines[0].quantity, 31.0);
+        assert_eq!(december.total, gbp(0.0));
+    }
+
+    #[tokio::test]
+    async fn testing_bill_for_unknown_meter() {
+        let path = Path("smart-meter-unknown".to_string());
+        let result = get_bill(path, query(Month::November), State(AppState::default())).await;
+
+        assert_eq!(
+            result.err(),
+            Some(ApiError::from(DataStoreError::UnknownSmartMeter(
+                "smart-meter-unknown".to_string()
+            )))
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use crate::datastore::error::DataStoreError;
+use crate::models::error::{FieldError, ProblemDetails};
+use axum::extract::rejection::{JsonRejection, PathRejection, QueryRejection};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderValue, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use std::error::Error;
+
+/// An error returned by a handler, rendered as an `application/problem+json` body
+///
+/// `problem_type` is a short slug identifying the kind of problem, exposed to
+/// clients as the `/problems/<slug>` type URI.
+#[derive(Debug, PartialEq)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub problem_type: &'static str,
+    pub detail: String,
+    pub errors: Vec<FieldError>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, problem_type: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            status,
+            problem_type,
+            detail: detail.into(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn bad_request(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "bad-request", detail)
+    }
+
+    pub fn not_found(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, "not-found", detail)
+    }
+
+    pub fn with_errors(mut self, errors: Vec<FieldError>) -> Self {
+        self.errors = errors;
+        self
+    }
+
+    pub fn to_problem_details(&self) -> ProblemDetails {
+        ProblemDetails {
+            problem_type: format!("/problems/{}", self.problem_type),
+            title: self
+                .status
+                .canonical_reason()
+                .unwrap_or("Error")
+                .to_string(),
+            status: self.status.as_u16(),
+            detail: self.detail.clone(),
+            errors: self.errors.clone(),
+        }
+    }
+}
+
+impl From<DataStoreError> for ApiError {
+    fn from(e: DataStoreError) -> Self {
+        match e {
+            DataStoreError::UnknownSmartMeter(_) => {
+                Self::new(StatusCode::NOT_FOUND, "unknown-smart-meter", e.to_string())
+            }
+            DataStoreError::DuplicateSmartMeter(_) => {
+                Self::new(StatusCode::CONFLICT, "duplicate-smart-meter", e.to_string())
+            }
+            DataStoreError::UnknownPricePlan(_) => {
+                Self::new(StatusCode::NOT_FOUND, "unknown-price-plan", e.to_string())
+            }
+            DataStoreError::DuplicatePricePlan(_) => {
+                Self::new(StatusCode::CONFLICT, "duplicate-price-plan", e.to_string())
+            }
+            DataStoreError::ConflictingReadings(_) => {
+                Self::new(StatusCode::CONFLICT, "conflicting-readings", e.to_string())
+            }
+            DataStoreError::Io(_) => Self::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "storage-error",
+                e.to_string(),
+            ),
+        }
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        let errors = field_error(&rejection).into_iter().collect();
+        Self::new(rejection.status(), "invalid-body", rejection.body_text()).with_errors(errors)
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        Self::new(rejection.status(), "invalid-query", rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        Self::new(rejection.status(), "invalid-path", rejection.body_text())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = serde_json::to_vec(&self.to_problem_details())
+            .expect("problem details are always serializable");
+        (
+            self.status,
+            [(
+                CONTENT_TYPE,
+                HeaderValue::from_static("application/problem+json"),
+            )],
+            body,
+        )
+            .into_response()
+    }
+}
+
+/// Responds to requests that do not match any route
+pub async fn route_not_found(uri: Uri) -> ApiError {
+    ApiError::not_found(format!("no endpoint matches {}", uri.path()))
+}
+
+/// Responds to requests that match a route but not any of its methods
+pub async fn method_not_allowed(uri: Uri) -> ApiError {
+    ApiError::new(
+        StatusCode::METHOD_NOT_ALLOWED,
+        "method-not-allowed",
+        format!("{} does not support this method", uri.path()),
+    )
+}
+
+/// Finds the field a JSON body failed to deserialize at, if the rejection names one
+fn field_error(rejection: &JsonRejection) -> Option<FieldError> {
+    let mut source = rejection.source();
+    while let Some(e) = source {
+        if let Some(e) = e.downcast_ref::<serde_path_to_error::Error<serde_json::Error>>() {
+            return Some(FieldError::new(e.path().to_string(), e.inner().to_string()));
+        }
+        source = e.source();
+    }
+    None
+}
@@ -1,2 +1,7 @@
+pub mod accounts;
+pub mod bills;
+pub mod error;
+pub mod extract;
 pub mod plans;
+pub mod prices;
 pub mod readings;
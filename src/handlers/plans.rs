@@ -1,82 +1,367 @@
+use crate::datastore::catalogue::{PricePlanDefinition, Problem, RateChangeDefinition};
+use crate::datastore::consumption::Consumption;
+use crate::datastore::error::DataStoreError;
+use crate::datastore::money::Money;
+use crate::datastore::plan::PricePlan;
 use crate::datastore::state::AppState;
-use crate::models::plans::{GetPricePlanCostResponse, GetRecommendationQueryParams};
-use axum::extract::{Path, Query, State};
+use crate::datastore::store::DataStore;
+use crate::handlers::error::ApiError;
+use crate::handlers::extract::{Json, Path, Query};
+use crate::models::error::FieldError;
+use crate::models::plans::{
+    Confidence, GetPricePlanCostQueryParams, GetPricePlanCostResponse,
+    GetRecommendationQueryParams, PlanPeriodCost, PlanRecommendation, RateBasis,
+};
+use axum::extract::State;
 use axum::http::StatusCode;
-use axum::Json;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::BTreeMap;
+use time::OffsetDateTime;
 
-/// Calculates hourly average costs across all price plans
+/// Calculates consumption and costs across all price plans
 ///
 /// # Returns
-/// A tuple containing:
+/// A response containing:
 /// * The current supplier's price plan ID
-/// * A map of price plan IDs to their average costs per hour
+/// * The energy consumed and exported in kWh, integrated from the stored readings
+/// * Maps of price plan IDs to their average costs per hour, consumption
+///   costs, standing charges, export credits and net total costs, at the rates
+///   in force at the time of each reading or, when `rates` is `current`, at
+///   today's rates
+/// * What the consumption actually cost, billing each part of it against the
+///   plan the account was on and the rates in force at the time, broken down
+///   by plan period
+///
+/// Retired plans are left out, except for the one the account is still on.
+///
+/// Responds with 404 Not Found when no account is registered for the meter.
 pub async fn get_price_plans(
     Path(smart_meter_id): Path<String>,
+    Query(query): Query<GetPricePlanCostQueryParams>,
     State(state): State<AppState>,
-) -> Result<Json<GetPricePlanCostResponse>, StatusCode> {
+) -> Result<Json<GetPricePlanCostResponse>, ApiError> {
     let data_store = state.db.lock().unwrap();
+    let account = data_store
+        .get_account(&smart_meter_id)
+        .ok_or_else(|| DataStoreError::UnknownSmartMeter(smart_meter_id.clone()))?;
+    let supplier_id = account.price_plan_at(OffsetDateTime::now_utc()).to_string();
 
     let stored_readings = data_store.get_readings(&smart_meter_id);
-    let mut price_plans = data_store.get_price_plans();
-    price_plans.sort();
+    let consumption = Consumption::from_readings(&stored_readings, query.method);
+    let price_plans = data_store.get_price_plans();
 
-    let comparisons = price_plans
-        .iter()
-        .map(|price_plan| {
-            let consumption_cost = price_plan.average_hourly_cost(&stored_readings);
-            (price_plan.supplier_id.to_string(), consumption_cost)
+    let plan_periods = account
+        .costs_by_period(&consumption, &price_plans, query.rounding, &state.vat_rates)
+        .into_iter()
+        .map(|(period, used, cost)| PlanPeriodCost {
+            price_plan_id: period.price_plan_id.to_string(),
+            from: period.from,
+            to: period.to,
+            consumption_kwh: used.total_kwh(),
+            consumption_cost: cost.consumption_cost,
+            standing_charge: cost.standing_charge,
+            export_credit: cost.export_credit,
+            cost: cost.total,
+            tax: cost.tax,
+            gross_cost: cost.gross,
         })
-        .collect::<BTreeMap<String, f64>>();
+        .collect::<Vec<PlanPeriodCost>>();
+    let currency = price_plans
+        .iter()
+        .find(|price_plan| price_plan.supplier_id == supplier_id)
+        .map(|price_plan| price_plan.currency)
+        .unwrap_or_default();
+
+    let mut comparisons = BTreeMap::new();
+    let mut consumption_costs = BTreeMap::new();
+    let mut standing_charges = BTreeMap::new();
+    let mut export_credits = BTreeMap::new();
+    let mut total_costs = BTreeMap::new();
+    let mut taxes = BTreeMap::new();
+    let mut gross_costs = BTreeMap::new();
+    let offered = price_plans
+        .iter()
+        .filter(|price_plan| !price_plan.retired || price_plan.supplier_id == supplier_id)
+        .map(|price_plan| at_rates(price_plan, query.rates));
+    for price_plan in offered {
+        let supplier_id = price_plan.supplier_id.to_string();
+        comparisons.insert(
+            supplier_id.clone(),
+            price_plan.average_hourly_cost(&consumption),
+        );
+        let cost = price_plan.cost_breakdown(&consumption, query.rounding, &state.vat_rates);
+        consumption_costs.insert(supplier_id.clone(), cost.consumption_cost);
+        standing_charges.insert(supplier_id.clone(), cost.standing_charge);
+        export_credits.insert(supplier_id.clone(), cost.export_credit);
+        total_costs.insert(supplier_id.clone(), cost.total);
+        taxes.insert(supplier_id.clone(), cost.tax);
+        gross_costs.insert(supplier_id, cost.gross);
+    }
 
     Ok(Json(GetPricePlanCostResponse {
         price_plans: comparisons,
-        supplier_id: data_store.get_account_supplier_id(&smart_meter_id),
+        supplier_id,
+        consumption_kwh: consumption.total_kwh(),
+        export_kwh: consumption.total_export_kwh(),
+        consumption_costs,
+        standing_charges,
+        export_credits,
+        total_costs,
+        taxes,
+        gross_costs,
+        actual_cost: Money::sum_in(currency, plan_periods.iter().map(|period| period.cost)),
+        actual_tax: Money::sum_in(currency, plan_periods.iter().map(|period| period.tax)),
+        actual_gross_cost: Money::sum_in(
+            currency,
+            plan_periods.iter().map(|period| period.gross_cost),
+        ),
+        plan_periods,
     }))
 }
 
+/// Ranks the price plans from cheapest to most expensive for a meter's usage
+///
+/// Plans are ranked by their projected annual cost, standing charges and VAT
+/// included, so a plan with a cheap unit rate but a high standing charge can
+/// lose out for meters that use little energy. Export credits are deducted, so a plan
+/// paying well for exports can win for households with solar generation.
+/// Plans are priced at today's rates unless `rates` is `historical`. Retired
+/// plans, and plans in a different currency from the account's current plan,
+/// are never recommended.
+///
+/// Each recommendation projects the plan's cost over a year and a month of
+/// usage like the meter's so far, VAT included, and the savings against the
+/// account's current plan. The confidence in the projections grows with the
+/// days of readings they are based on.
+///
+/// Responds with 404 Not Found when no account is registered for the meter.
 pub async fn get_recommended_plans(
     Path(smart_meter_id): Path<String>,
     Query(query): Query<GetRecommendationQueryParams>,
     State(state): State<AppState>,
-) -> Result<Json<Vec<HashMap<String, f64>>>, StatusCode> {
+) -> Result<Json<Vec<PlanRecommendation>>, ApiError> {
     let data_store = state.db.lock().unwrap();
+    let supplier_id =
+        data_store.get_account_supplier_id(&smart_meter_id, OffsetDateTime::now_utc())?;
 
-    let mut price_plans = data_store.get_price_plans();
-    price_plans.sort();
+    let price_plans = data_store.get_price_plans();
+    let currency = price_plans
+        .iter()
+        .find(|price_plan| price_plan.supplier_id == supplier_id)
+        .map(|price_plan| price_plan.currency)
+        .unwrap_or_default();
     let stored_readings = data_store.get_readings(&smart_meter_id);
+    let consumption = Consumption::from_readings(&stored_readings, query.method);
     let limit = query.limit;
+    let current_annual_cost = price_plans
+        .iter()
+        .find(|price_plan| price_plan.supplier_id == supplier_id)
+        .map(|price_plan| {
+            at_rates(price_plan, query.rates).projected_annual_cost(&consumption, &state.vat_rates)
+        });
+    let history_days = consumption.total_hours() / 24.0;
 
-    let response: Vec<HashMap<String, f64>> = price_plans
+    let mut recommendations = price_plans
         .iter()
-        .take(limit as usize)
+        .filter(|price_plan| !price_plan.retired && price_plan.currency == currency)
+        .map(|price_plan| at_rates(price_plan, query.rates))
         .map(|price_plan| {
-            let cost = price_plan.average_hourly_cost(&stored_readings);
-            let mut plan_recommendation = HashMap::new();
-            plan_recommendation.insert(price_plan.supplier_id.clone(), cost);
-            plan_recommendation
+            let cost = price_plan.cost_breakdown(&consumption, query.rounding, &state.vat_rates);
+            let annual_cost = price_plan.projected_annual_cost(&consumption, &state.vat_rates);
+            let annual_savings = current_annual_cost.map(|current| current - annual_cost);
+            PlanRecommendation {
+                price_plan_id: price_plan.supplier_id.clone(),
+                average_hourly_cost: price_plan.average_hourly_cost(&consumption),
+                consumption_cost: cost.consumption_cost,
+                standing_charge: cost.standing_charge,
+                export_credit: cost.export_credit,
+                total_cost: cost.total,
+                tax: cost.tax,
+                gross_cost: cost.gross,
+                projected_annual_cost: annual_cost.round(),
+                projected_monthly_cost: (annual_cost * (1.0 / 12.0)).round(),
+                annual_savings: annual_savings.map(Money::round),
+                monthly_savings: annual_savings.map(|savings| (savings * (1.0 / 12.0)).round()),
+                confidence: Confidence::for_history(history_days),
+                history_days,
+            }
         })
+        .collect::<Vec<PlanRecommendation>>();
+    recommendations.sort_by_key(|recommendation| recommendation.projected_annual_cost);
+    recommendations.truncate(limit as usize);
+
+    Ok(Json(recommendations))
+}
+
+/// Lists every price plan, including retired ones
+pub async fn list_price_plans(State(state): State<AppState>) -> Json<Vec<PricePlanDefinition>> {
+    let data_store = state.db.lock().unwrap();
+    let price_plans = data_store
+        .get_price_plans()
+        .iter()
+        .map(PricePlanDefinition::from)
         .collect();
+    Json(price_plans)
+}
+
+/// Returns a single price plan, whether or not it is retired
+///
+/// Responds with 404 Not Found when no plan has the id.
+pub async fn get_price_plan(
+    Path(price_plan_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<PricePlanDefinition>, ApiError> {
+    let data_store = state.db.lock().unwrap();
+    let price_plan = find_price_plan(&*data_store, &price_plan_id)?;
+    Ok(Json(PricePlanDefinition::from(&price_plan)))
+}
 
+/// Adds a price plan
+///
+/// Responds with 201 Created, with 409 Conflict when a plan with the same id
+/// already exists, or with 422 Unprocessable Entity when the plan is invalid.
+pub async fn create_price_plan(
+    State(state): State<AppState>,
+    Json(body): Json<PricePlanDefinition>,
+) -> Result<(StatusCode, Json<PricePlanDefinition>), ApiError> {
+    let price_plan = body.to_price_plan().map_err(invalid_definition)?;
+
+    let mut data_store = state.db.lock().unwrap();
+    data_store.add_price_plan(price_plan)?;
+    Ok((StatusCode::CREATED, Json(body)))
+}
+
+/// Replaces a price plan with the one in the request body
+///
+/// Responds with 404 Not Found when no plan has the id, or with 422
+/// Unprocessable Entity when the plan is invalid or its id differs from the path.
+pub async fn update_price_plan(
+    Path(price_plan_id): Path<String>,
+    State(state): State<AppState>,
+    Json(body): Json<PricePlanDefinition>,
+) -> Result<Json<PricePlanDefinition>, ApiError> {
+    if body.id != price_plan_id {
+        return Err(invalid_price_plan(vec![FieldError::new(
+            "id",
+            "must match the price plan id in the path",
+        )]));
+    }
+    let price_plan = body.to_price_plan().map_err(invalid_definition)?;
+
+    let mut data_store = state.db.lock().unwrap();
+    data_store.update_price_plan(price_plan)?;
+    Ok(Json(body))
+}
+
+/// Retires a price plan so it is no longer offered or recommended
+///
+/// Accounts already on the plan keep it, so it remains resolvable by id.
+/// Responds with 404 Not Found when no plan has the id.
+pub async fn retire_price_plan(
+    Path(price_plan_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<PricePlanDefinition>, ApiError> {
+    let mut data_store = state.db.lock().unwrap();
+    let mut price_plan = find_price_plan(&*data_store, &price_plan_id)?;
+    price_plan.retired = true;
+    let response = PricePlanDefinition::from(&price_plan);
+    data_store.update_price_plan(price_plan)?;
     Ok(Json(response))
 }
 
+/// Gives a price plan new rates from a given time, keeping its current rates
+/// for consumption before then
+///
+/// Responds with 201 Created, with 404 Not Found when no plan has the id, or
+/// with 422 Unprocessable Entity when the rates are invalid or do not take
+/// effect after the current ones.
+pub async fn change_price_plan_rates(
+    Path(price_plan_id): Path<String>,
+    State(state): State<AppState>,
+    Json(body): Json<RateChangeDefinition>,
+) -> Result<(StatusCode, Json<PricePlanDefinition>), ApiError> {
+    let mut data_store = state.db.lock().unwrap();
+    let mut price_plan = find_price_plan(&*data_store, &price_plan_id)?;
+    body.apply_to(&mut price_plan).map_err(invalid_definition)?;
+    let response = PricePlanDefinition::from(&price_plan);
+    data_store.update_price_plan(price_plan)?;
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+fn at_rates(price_plan: &PricePlan, rates: RateBasis) -> PricePlan {
+    match rates {
+        RateBasis::Historical => price_plan.clone(),
+        RateBasis::Current => price_plan.at_rates_in_force(OffsetDateTime::now_utc()),
+    }
+}
+
+fn find_price_plan(data_store: &dyn DataStore, price_plan_id: &str) -> Result<PricePlan, ApiError> {
+    data_store
+        .get_price_plan(price_plan_id)
+        .ok_or_else(|| DataStoreError::UnknownPricePlan(price_plan_id.to_string()).into())
+}
+
+fn invalid_price_plan(errors: Vec<FieldError>) -> ApiError {
+    ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "invalid-price-plan",
+        "the price plan is not valid",
+    )
+    .with_errors(errors)
+}
+
+/// Reports the problems found with a plan or rate definition against the
+/// fields they concern
+fn invalid_definition(problems: Vec<Problem>) -> ApiError {
+    invalid_price_plan(
+        problems
+            .into_iter()
+            .map(|problem| FieldError::new(problem.field, problem.message))
+            .collect(),
+    )
+}
+
 #[cfg(test)]
 mod tests {
-    use std::collections::{BTreeMap, HashMap};
+    use std::collections::BTreeMap;
 
+    use crate::datastore::catalogue::RateChangeDefinition;
+    use crate::datastore::consumption::IntegrationMethod;
+    use crate::datastore::money::{Currency, Money, Rounding};
     use crate::datastore::reading::ElectricityReading;
     use crate::datastore::state::AppState;
-    use crate::handlers::plans::{get_price_plans, get_recommended_plans};
-    use crate::models::plans::{GetPricePlanCostResponse, GetRecommendationQueryParams};
-    use axum::extract::{Path, Query, State};
-    use axum::Json;
+    use crate::datastore::tax::VatCategory;
+    use crate::handlers::extract::{Json, Path, Query};
+    use crate::handlers::plans::{
+        change_price_plan_rates, get_price_plans, get_recommended_plans, retire_price_plan,
+    };
+    use crate::models::plans::{
+        Confidence, GetPricePlanCostQueryParams, GetPricePlanCostResponse,
+        GetRecommendationQueryParams, PlanPeriodCost, PlanRecommendation, RateBasis,
+    };
+    use axum::extract::State;
+    use axum::http::StatusCode;
     use time::macros::datetime;
 
     fn make_state() -> AppState {
         AppState::default()
     }
 
+    fn gbp(amount: f64) -> Money {
+        Money::from_f64(amount, Currency::Gbp)
+    }
+
+    fn ranking(recommended: &[PlanRecommendation]) -> Vec<(&str, f64)> {
+        recommended
+            .iter()
+            .map(|plan| {
+                (
+                    plan.price_plan_id.as_str(),
+                    plan.average_hourly_cost.to_f64(),
+                )
+            })
+            .collect()
+    }
+
     #[tokio::test]
     async fn testing_getting_price_plans() {
         let state = make_state();
@@ -86,27 +371,74 @@ mod tests {
                 ElectricityReading {
                     time: datetime!(2020-11-29 08:00:00 UTC),
                     reading: 1.0,
+                    export: None,
                 },
                 ElectricityReading {
                     time: datetime!(2020-11-29 08:01:00 UTC),
                     reading: 2.0,
+                    export: None,
                 },
                 ElectricityReading {
                     time: datetime!(2020-11-29 08:02:00 UTC),
                     reading: 3.0,
+                    export: None,
                 },
             ];
-            db.insert_readings("smart-meter-0".to_string(), readings);
+            db.insert_readings("smart-meter-0".to_string(), readings)
+                .unwrap();
         }
         let path = Path("smart-meter-0".to_string());
-        let Json(result) = get_price_plans(path, State(state)).await.unwrap();
+        let query = Query(GetPricePlanCostQueryParams::default());
+        let Json(result) = get_price_plans(path, query, State(state)).await.unwrap();
         let mut expected_plans = BTreeMap::new();
-        expected_plans.insert("price-plan-0".to_string(), 600.0);
-        expected_plans.insert("price-plan-1".to_string(), 120.0);
-        expected_plans.insert("price-plan-2".to_string(), 60.0);
+        expected_plans.insert("price-plan-0".to_string(), gbp(20.0));
+        expected_plans.insert("price-plan-1".to_string(), gbp(4.0));
+        expected_plans.insert("price-plan-2".to_string(), gbp(2.0));
+        let mut expected_costs = BTreeMap::new();
+        expected_costs.insert("price-plan-0".to_string(), gbp(0.67));
+        expected_costs.insert("price-plan-1".to_string(), gbp(0.13));
+        expected_costs.insert("price-plan-2".to_string(), gbp(0.07));
+        // VAT at the domestic rate of 5%
+        let mut expected_taxes = BTreeMap::new();
+        expected_taxes.insert("price-plan-0".to_string(), gbp(0.03));
+        expected_taxes.insert("price-plan-1".to_string(), gbp(0.01));
+        expected_taxes.insert("price-plan-2".to_string(), gbp(0.0));
+        let mut expected_gross_costs = BTreeMap::new();
+        expected_gross_costs.insert("price-plan-0".to_string(), gbp(0.70));
+        expected_gross_costs.insert("price-plan-1".to_string(), gbp(0.14));
+        expected_gross_costs.insert("price-plan-2".to_string(), gbp(0.07));
         let expected_result = GetPricePlanCostResponse {
             price_plans: expected_plans,
             supplier_id: "price-plan-0".to_string(),
+            consumption_kwh: 1.0 / 15.0,
+            export_kwh: 0.0,
+            consumption_costs: expected_costs.clone(),
+            standing_charges: expected_costs
+                .keys()
+                .map(|id| (id.clone(), gbp(0.0)))
+                .collect(),
+            export_credits: expected_costs
+                .keys()
+                .map(|id| (id.clone(), gbp(0.0)))
+                .collect(),
+            total_costs: expected_costs,
+            taxes: expected_taxes,
+            gross_costs: expected_gross_costs,
+            actual_cost: Some(gbp(0.67)),
+            actual_tax: Some(gbp(0.03)),
+            actual_gross_cost: Some(gbp(0.70)),
+            plan_periods: vec![PlanPeriodCost {
+                price_plan_id: "price-plan-0".to_string(),
+                from: None,
+                to: None,
+                consumption_kwh: 1.0 / 15.0,
+                consumption_cost: gbp(0.67),
+                standing_charge: gbp(0.0),
+                export_credit: gbp(0.0),
+                cost: gbp(0.67),
+                tax: gbp(0.03),
+                gross_cost: gbp(0.70),
+            }],
         };
 
         assert_eq!(expected_result, result);
@@ -121,29 +453,440 @@ mod tests {
                 ElectricityReading {
                     time: datetime!(2020-11-29 08:00:00 UTC),
                     reading: 1.0,
+                    export: None,
                 },
                 ElectricityReading {
                     time: datetime!(2020-11-29 08:01:00 UTC),
                     reading: 2.0,
+                    export: None,
                 },
                 ElectricityReading {
                     time: datetime!(2020-11-29 08:02:00 UTC),
                     reading: 3.0,
+                    export: None,
                 },
             ];
-            db.insert_readings("smart-meter-0".to_string(), readings);
+            db.insert_readings("smart-meter-0".to_string(), readings)
+                .unwrap();
         }
         let path = Path("smart-meter-0".to_string());
-        let limit = Query(GetRecommendationQueryParams { limit: 2 });
+        let limit = Query(GetRecommendationQueryParams {
+            limit: 2,
+            method: IntegrationMethod::Trapezoidal,
+            rates: RateBasis::Current,
+            rounding: Rounding::PerLine,
+        });
 
         let Json(result) = get_recommended_plans(path, limit, State(state))
             .await
             .unwrap();
-        let expected_result: Vec<HashMap<String, f64>> = vec![
-            HashMap::from([("price-plan-2".to_string(), 60.0)]),
-            HashMap::from([("price-plan-1".to_string(), 120.0)]),
+        // Two minutes of readings, at 2.1 an hour VAT included on price-plan-2,
+        // 4.2 on price-plan-1 and 21 on the current price-plan-0
+        let history_days = 120.0 / 3600.0 / 24.0;
+        let expected_result = vec![
+            PlanRecommendation {
+                price_plan_id: "price-plan-2".to_string(),
+                average_hourly_cost: gbp(2.0),
+                consumption_cost: gbp(0.07),
+                standing_charge: gbp(0.0),
+                export_credit: gbp(0.0),
+                total_cost: gbp(0.07),
+                tax: gbp(0.0),
+                gross_cost: gbp(0.07),
+                projected_annual_cost: gbp(18408.6),
+                projected_monthly_cost: gbp(1534.05),
+                annual_savings: Some(gbp(165677.4)),
+                monthly_savings: Some(gbp(13806.45)),
+                confidence: Confidence::Low,
+                history_days,
+            },
+            PlanRecommendation {
+                price_plan_id: "price-plan-1".to_string(),
+                average_hourly_cost: gbp(4.0),
+                consumption_cost: gbp(0.13),
+                standing_charge: gbp(0.0),
+                export_credit: gbp(0.0),
+                total_cost: gbp(0.13),
+                tax: gbp(0.01),
+                gross_cost: gbp(0.14),
+                projected_annual_cost: gbp(36817.2),
+                projected_monthly_cost: gbp(3068.1),
+                annual_savings: Some(gbp(147268.8)),
+                monthly_savings: Some(gbp(12272.4)),
+                confidence: Confidence::Low,
+                history_days,
+            },
         ];
 
         assert_eq!(expected_result, result);
     }
+
+    #[tokio::test]
+    async fn testing_costs_follow_plan_switches() {
+        let state = make_state();
+        {
+            let mut db = state.db.lock().unwrap();
+            let readings = vec![
+                ElectricityReading {
+                    time: datetime!(2020-11-29 08:00:00 UTC),
+                    reading: 1.0,
+                    export: None,
+                },
+                ElectricityReading {
+                    time: datetime!(2020-11-29 10:00:00 UTC),
+                    reading: 1.0,
+                    export: None,
+                },
+            ];
+            db.insert_readings("smart-meter-0".to_string(), readings)
+                .unwrap();
+            let mut account = db.get_account(&"smart-meter-0".to_string()).unwrap();
+            account.switch_price_plan("price-plan-2", datetime!(2020-11-29 09:00:00 UTC));
+            db.update_account("smart-meter-0".to_string(), account)
+                .unwrap();
+        }
+        let path = Path("smart-meter-0".to_string());
+        let query = Query(GetPricePlanCostQueryParams::default());
+
+        let Json(result) = get_price_plans(path, query, State(state)).await.unwrap();
+
+        assert_eq!(result.supplier_id, "price-plan-2");
+        assert_eq!(result.actual_cost, Some(gbp(11.0)));
+        assert_eq!(
+            result
+                .plan_periods
+                .iter()
+                .map(|period| (period.price_plan_id.as_str(), period.cost))
+                .collect::<Vec<(&str, Money)>>(),
+            vec![("price-plan-0", gbp(10.0)), ("price-plan-2", gbp(1.0))]
+        );
+    }
+
+    #[tokio::test]
+    async fn testing_plans_in_other_currencies() {
+        let state = make_state();
+        {
+            let mut db = state.db.lock().unwrap();
+            let readings = vec![
+                ElectricityReading::new(datetime!(2020-11-29 08:00:00 UTC), 1.0),
+                ElectricityReading::new(datetime!(2020-11-29 10:00:00 UTC), 1.0),
+            ];
+            db.insert_readings("smart-meter-0".to_string(), readings)
+                .unwrap();
+            let mut price_plan = db.get_price_plan("price-plan-2").unwrap();
+            price_plan.currency = Currency::Eur;
+            price_plan.vat_category = VatCategory::Standard;
+            db.update_price_plan(price_plan).unwrap();
+            let mut account = db.get_account(&"smart-meter-0".to_string()).unwrap();
+            account.switch_price_plan("price-plan-2", datetime!(2020-11-29 09:00:00 UTC));
+            db.update_account("smart-meter-0".to_string(), account)
+                .unwrap();
+        }
+        let euros = |amount| Money::from_f64(amount, Currency::Eur);
+
+        let query = Query(GetPricePlanCostQueryParams::default());
+        let Json(compared) = get_price_plans(
+            Path("smart-meter-0".to_string()),
+            query,
+            State(state.clone()),
+        )
+        .await
+        .unwrap();
+        let query = Query(GetRecommendationQueryParams {
+            limit: 3,
+            method: IntegrationMethod::Trapezoidal,
+            rates: RateBasis::Current,
+            rounding: Rounding::PerLine,
+        });
+        let Json(recommended) =
+            get_recommended_plans(Path("smart-meter-0".to_string()), query, State(state))
+                .await
+                .unwrap();
+
+        assert_eq!(compared.total_costs["price-plan-0"], gbp(20.0));
+        assert_eq!(compared.taxes["price-plan-0"], gbp(1.0));
+        assert_eq!(compared.total_costs["price-plan-2"], euros(2.0));
+        assert_eq!(compared.taxes["price-plan-2"], euros(0.4));
+        assert_eq!(compared.gross_costs["price-plan-2"], euros(2.4));
+        assert_eq!(compared.actual_cost, None);
+        assert_eq!(ranking(&recommended), vec![("price-plan-2", 1.0)]);
+    }
+
+    #[tokio::test]
+    async fn testing_retired_plans_are_not_recommended() {
+        let state = make_state();
+        {
+            let mut db = state.db.lock().unwrap();
+            let readings = vec![
+                ElectricityReading {
+                    time: datetime!(2020-11-29 08:00:00 UTC),
+                    reading: 1.0,
+                    export: None,
+                },
+                ElectricityReading {
+                    time: datetime!(2020-11-29 09:00:00 UTC),
+                    reading: 1.0,
+                    export: None,
+                },
+            ];
+            db.insert_readings("smart-meter-3".to_string(), readings)
+                .unwrap();
+        }
+        retire_price_plan(Path("price-plan-2".to_string()), State(state.clone()))
+            .await
+            .unwrap();
+
+        let query = Query(GetRecommendationQueryParams {
+            limit: 3,
+            method: IntegrationMethod::Trapezoidal,
+            rates: RateBasis::Current,
+            rounding: Rounding::PerLine,
+        });
+        let Json(recommended) = get_recommended_plans(
+            Path("smart-meter-3".to_string()),
+            query,
+            State(state.clone()),
+        )
+        .await
+        .unwrap();
+        let query = Query(GetPricePlanCostQueryParams::default());
+        let Json(compared) =
+            get_price_plans(Path("smart-meter-3".to_string()), query, State(state))
+                .await
+                .unwrap();
+
+        assert_eq!(
+            ranking(&recommended),
+            vec![("price-plan-1", 2.0), ("price-plan-0", 10.0)]
+        );
+        assert!(compared.price_plans.contains_key("price-plan-2"));
+    }
+
+    #[tokio::test]
+    async fn testing_standing_charges_change_ranking_for_low_usage() {
+        let state = make_state();
+        {
+            let mut db = state.db.lock().unwrap();
+            // 0.1 kWh over a day
+            let readings = vec![
+                ElectricityReading {
+                    time: datetime!(2020-11-29 00:00:00 UTC),
+                    reading: 0.1 / 24.0,
+                    export: None,
+                },
+                ElectricityReading {
+                    time: datetime!(2020-11-30 00:00:00 UTC),
+                    reading: 0.1 / 24.0,
+                    export: None,
+                },
+            ];
+            db.insert_readings("smart-meter-0".to_string(), readings)
+                .unwrap();
+            let price_plan = db
+                .get_price_plan("price-plan-2")
+                .unwrap()
+                .with_standing_charge(24.0);
+            db.update_price_plan(price_plan).unwrap();
+        }
+
+        let query = Query(GetRecommendationQueryParams {
+            limit: 3,
+            method: IntegrationMethod::Trapezoidal,
+            rates: RateBasis::Current,
+            rounding: Rounding::PerLine,
+        });
+        let Json(recommended) = get_recommended_plans(
+            Path("smart-meter-0".to_string()),
+            query,
+            State(state.clone()),
+        )
+        .await
+        .unwrap();
+        let query = Query(GetPricePlanCostQueryParams::default());
+        let Json(compared) =
+            get_price_plans(Path("smart-meter-0".to_string()), query, State(state))
+                .await
+                .unwrap();
+
+        assert_eq!(
+            recommended
+                .iter()
+                .map(|plan| plan.price_plan_id.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["price-plan-1", "price-plan-0", "price-plan-2"]
+        );
+        assert_eq!(recommended[2].standing_charge, gbp(24.0));
+        assert_eq!(compared.standing_charges["price-plan-2"], gbp(24.0));
+        assert_eq!(
+            compared.total_costs["price-plan-2"],
+            compared.consumption_costs["price-plan-2"] + gbp(24.0)
+        );
+    }
+
+    #[tokio::test]
+    async fn testing_plans_are_ranked_by_projected_annual_cost() {
+        let state = make_state();
+        {
+            let mut db = state.db.lock().unwrap();
+            let readings = vec![
+                ElectricityReading::new(datetime!(2020-11-29 08:00:00 UTC), 1.0),
+                ElectricityReading::new(datetime!(2020-11-29 09:00:00 UTC), 1.0),
+            ];
+            db.insert_readings("smart-meter-0".to_string(), readings)
+                .unwrap();
+            // Cheaper than price-plan-2 before VAT, dearer with VAT at the standard rate
+            let mut price_plan = db.get_price_plan("price-plan-1").unwrap();
+            price_plan.unit_rate = 0.9;
+            price_plan.vat_category = VatCategory::Standard;
+            db.update_price_plan(price_plan).unwrap();
+        }
+
+        let query = Query(GetRecommendationQueryParams {
+            limit: 2,
+            method: IntegrationMethod::Trapezoidal,
+            rates: RateBasis::Current,
+            rounding: Rounding::PerLine,
+        });
+        let Json(recommended) =
+            get_recommended_plans(Path("smart-meter-0".to_string()), query, State(state))
+                .await
+                .unwrap();
+
+        assert_eq!(
+            ranking(&recommended),
+            vec![("price-plan-2", 1.0), ("price-plan-1", 0.9)]
+        );
+    }
+
+    #[tokio::test]
+    async fn testing_export_credits_are_netted_against_costs() {
+        let state = make_state();
+        {
+            let mut db = state.db.lock().unwrap();
+            // 2 kWh imported and 2 kWh exported over two hours
+            let readings = vec![
+                ElectricityReading::new(datetime!(2020-11-29 08:00:00 UTC), 1.0).with_export(1.0),
+                ElectricityReading::new(datetime!(2020-11-29 10:00:00 UTC), 1.0).with_export(1.0),
+            ];
+            db.insert_readings("smart-meter-0".to_string(), readings)
+                .unwrap();
+            let price_plan = db
+                .get_price_plan("price-plan-0")
+                .unwrap()
+                .with_export_rate(15.0);
+            db.update_price_plan(price_plan).unwrap();
+        }
+
+        let query = Query(GetRecommendationQueryParams {
+            limit: 3,
+            method: IntegrationMethod::Trapezoidal,
+            rates: RateBasis::Current,
+            rounding: Rounding::PerLine,
+        });
+        let Json(recommended) = get_recommended_plans(
+            Path("smart-meter-0".to_string()),
+            query,
+            State(state.clone()),
+        )
+        .await
+        .unwrap();
+        let query = Query(GetPricePlanCostQueryParams::default());
+        let Json(compared) =
+            get_price_plans(Path("smart-meter-0".to_string()), query, State(state))
+                .await
+                .unwrap();
+
+        assert_eq!(
+            ranking(&recommended),
+            vec![
+                ("price-plan-0", -5.0),
+                ("price-plan-2", 1.0),
+                ("price-plan-1", 2.0)
+            ]
+        );
+        assert_eq!(recommended[0].export_credit, gbp(30.0));
+        assert_eq!(compared.export_kwh, 2.0);
+        assert_eq!(compared.export_credits["price-plan-0"], gbp(30.0));
+        assert_eq!(compared.total_costs["price-plan-0"], gbp(-10.0));
+        assert_eq!(compared.actual_cost, Some(gbp(-10.0)));
+        assert_eq!(compared.plan_periods[0].consumption_cost, gbp(20.0));
+        assert_eq!(compared.plan_periods[0].export_credit, gbp(30.0));
+    }
+
+    #[tokio::test]
+    async fn testing_costs_at_historical_and_current_rates() {
+        let state = make_state();
+        {
+            let mut db = state.db.lock().unwrap();
+            let readings = vec![
+                ElectricityReading {
+                    time: datetime!(2020-11-29 08:00:00 UTC),
+                    reading: 1.0,
+                    export: None,
+                },
+                ElectricityReading {
+                    time: datetime!(2020-11-29 10:00:00 UTC),
+                    reading: 1.0,
+                    export: None,
+                },
+            ];
+            db.insert_readings("smart-meter-0".to_string(), readings)
+                .unwrap();
+        }
+        let change = RateChangeDefinition {
+            valid_from: datetime!(2020-11-29 09:00:00 UTC),
+            unit_rate: 20.0,
+            standing_charge: 0.0,
+            export_rate: 0.0,
+            multipliers: BTreeMap::new(),
+            rate_bands: Vec::new(),
+            tiers: Vec::new(),
+        };
+        let (status, Json(changed)) = change_price_plan_rates(
+            Path("price-plan-0".to_string()),
+            State(state.clone()),
+            Json(change.clone()),
+        )
+        .await
+        .unwrap();
+        let repeated = change_price_plan_rates(
+            Path("price-plan-0".to_string()),
+            State(state.clone()),
+            Json(change),
+        )
+        .await
+        .unwrap_err();
+
+        let compare = |rates| {
+            get_price_plans(
+                Path("smart-meter-0".to_string()),
+                Query(GetPricePlanCostQueryParams {
+                    rates,
+                    ..Default::default()
+                }),
+                State(state.clone()),
+            )
+        };
+        let Json(historical) = compare(RateBasis::Historical).await.unwrap();
+        let Json(current) = compare(RateBasis::Current).await.unwrap();
+
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(changed.unit_rate, 20.0);
+        assert_eq!(changed.previous_versions[0].unit_rate, 10.0);
+        assert_eq!(repeated.status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(historical.total_costs["price-plan-0"], gbp(30.0));
+        assert_eq!(current.total_costs["price-plan-0"], gbp(40.0));
+        assert_eq!(current.actual_cost, Some(gbp(30.0)));
+    }
+
+    #[tokio::test]
+    async fn testing_getting_price_plans_for_unknown_meter() {
+        let state = make_state();
+        let path = Path("unknown-meter".to_string());
+        let query = Query(GetPricePlanCostQueryParams::default());
+
+        let result = get_price_plans(path, query, State(state)).await;
+
+        assert_eq!(result.unwrap_err().status, StatusCode::NOT_FOUND);
+    }
 }
@@ -0,0 +1,102 @@
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderMap, StatusCode};
+
+use crate::datastore::error::DataStoreError;
+use crate::datastore::plan::HalfHourPrice;
+use crate::datastore::state::AppState;
+use crate::handlers::error::ApiError;
+use crate::handlers::extract::{Json, Path, Query};
+use crate::models::error::FieldError;
+use crate::models::prices::{
+    GetPricesQueryParams, GetPricesResponse, UploadPricesRequest, UploadPricesResponse,
+};
+
+/// Returns the half-hourly prices imported for a plan, in chronological order,
+/// optionally restricted to half hours starting at or after `from` and before `to`
+///
+/// Responds with 404 Not Found when no plan has the id.
+pub async fn get_half_hourly_prices(
+    Path(price_plan_id): Path<String>,
+    Query(query): Query<GetPricesQueryParams>,
+    State(state): State<AppState>,
+) -> Result<Json<GetPricesResponse>, ApiError> {
+    let data_store = state.db.lock().unwrap();
+    let price_plan = data_store
+        .get_price_plan(&price_plan_id)
+        .ok_or_else(|| DataStoreError::UnknownPricePlan(price_plan_id.clone()))?;
+
+    let prices = price_plan
+        .half_hourly_prices
+        .iter()
+        .filter(|(start, _)| query.from.is_none_or(|from| **start >= from))
+        .filter(|(start, _)| query.to.is_none_or(|to| **start < to))
+        .map(|(start, unit_rate)| HalfHourPrice::new(*start, *unit_rate))
+        .collect();
+    Ok(Json(GetPricesResponse {
+        price_plan_id,
+        prices,
+    }))
+}
+
+/// Imports half-hourly prices for an agile plan, replacing any the plan
+/// already has for the same half hours
+///
+/// The body is JSON, or CSV with a `start,unit_rate` header when sent as
+/// `text/csv`. Nothing is stored unless every price is valid. Responds with
+/// 404 Not Found when no plan has the id, with 415 Unsupported Media Type for
+/// other content types, or with 422 Unprocessable Entity when a price is invalid.
+pub async fn upload_half_hourly_prices(
+    Path(price_plan_id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<UploadPricesResponse>, ApiError> {
+    let request = parse_upload(&headers, &body)?;
+    let errors = request.validate();
+    if !errors.is_empty() {
+        return Err(invalid_prices(errors));
+    }
+
+    let response = UploadPricesResponse {
+        stored: request.prices.len(),
+        from: request.prices.iter().map(|price| price.start).min(),
+        to: request.prices.iter().filter_map(HalfHourPrice::end).max(),
+    };
+    let mut data_store = state.db.lock().unwrap();
+    data_store.add_half_hourly_prices(&price_plan_id, request.prices)?;
+    Ok(Json(response))
+}
+
+/// Reads an upload as CSV or JSON depending on its content type
+fn parse_upload(headers: &HeaderMap, body: &[u8]) -> Result<UploadPricesRequest, ApiError> {
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    if content_type.starts_with("text/csv") {
+        let text = std::str::from_utf8(body)
+            .map_err(|_| ApiError::bad_request("the CSV body is not valid UTF-8"))?;
+        UploadPricesRequest::from_csv(text).map_err(invalid_prices)
+    } else if content_type.starts_with("application/json") {
+        let axum::Json(request) = axum::Json::<UploadPricesRequest>::from_bytes(body)?;
+        Ok(request)
+    } else {
+        Err(ApiError::new(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "invalid-body",
+            "expected a request with Content-Type: application/json or text/csv",
+        ))
+    }
+}
+
+fn invalid_prices(errors: Vec<FieldError>) -> ApiError {
+    ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "invalid-prices",
+        "the half-hourly prices are not valid",
+    )
+    .with_errors(errors)
+}
@@ -1,59 +1,188 @@
-use axum::extract::{Path, State};
+use axum::extract::State;
 use axum::http::StatusCode;
-use axum::Json;
+use time::OffsetDateTime;
 
-use crate::datastore::reading::ElectricityReading;
+use crate::datastore::error::DataStoreError;
 use crate::datastore::state::AppState;
-use crate::models::readings::{CreateElectricityReadingsRequest, GetElectricityReadingResponse};
+use crate::datastore::store::InsertSummary;
+use crate::handlers::error::ApiError;
+use crate::handlers::extract::{Json, Path, Query};
+use crate::models::error::FieldError;
+use crate::models::readings::{
+    CreateElectricityReadingsRequest, CreateElectricityReadingsResponse, CreateReadingsQueryParams,
+    GetElectricityReadingResponse, GetElectricityReadingsPageResponse, GetReadingsQueryParams,
+    ReadingsCursor,
+};
+use crate::models::validation::ValidationMode;
 
+/// Number of readings returned per page when the client does not ask for a limit
+pub const DEFAULT_PAGE_LIMIT: usize = 100;
+
+/// Largest page a client can ask for
+pub const MAX_PAGE_LIMIT: usize = 1000;
+
+/// Returns a page of the stored readings for a meter in chronological order,
+/// optionally restricted to those taken at or after `from` and before `to`
+///
+/// When more readings follow, the response carries a `next_cursor` to pass back
+/// as `cursor` to fetch the next page.
 pub async fn get_readings(
     Path(smart_meter_id): Path<String>,
+    Query(query): Query<GetReadingsQueryParams>,
     State(state): State<AppState>,
-) -> Result<Json<Vec<GetElectricityReadingResponse>>, StatusCode> {
+) -> Result<Json<GetElectricityReadingsPageResponse>, ApiError> {
+    if matches!((query.from, query.to), (Some(from), Some(to)) if from > to) {
+        return Err(ApiError::bad_request("from must not be later than to"));
+    }
+    let limit = match query.limit {
+        Some(0) => return Err(ApiError::bad_request("limit must be at least 1")),
+        Some(limit) => limit.min(MAX_PAGE_LIMIT),
+        None => DEFAULT_PAGE_LIMIT,
+    };
+    let after = match query.cursor.as_deref() {
+        Some(cursor) => Some(
+            ReadingsCursor::decode(cursor)
+                .ok_or_else(|| ApiError::bad_request("cursor is not valid"))?
+                .0,
+        ),
+        None => None,
+    };
+
     let data_store = state.db.lock().unwrap();
 
-    let stored_readings = data_store
-        .get_readings(&smart_meter_id)
+    let page = data_store.get_readings_page(&smart_meter_id, query.from, query.to, after, limit);
+    let next_cursor = page
+        .readings
+        .last()
+        .filter(|_| page.has_more)
+        .map(|r| ReadingsCursor(r.time).encode());
+    let items = page
+        .readings
         .iter()
         .map(GetElectricityReadingResponse::from)
         .collect::<Vec<GetElectricityReadingResponse>>();
 
-    Ok(Json(stored_readings))
+    Ok(Json(GetElectricityReadingsPageResponse {
+        items,
+        next_cursor,
+        limit,
+    }))
 }
 
+/// Validates and stores a batch of readings for a meter
+///
+/// In strict mode any invalid reading rejects the whole batch with 422
+/// Unprocessable Entity. In lenient mode invalid readings are dropped, the rest
+/// are stored, and the dropped ones are listed in the response.
+///
+/// Readings identical to stored ones are skipped, so retried uploads are safe.
+/// Readings that differ from a stored one at the same time are resolved with
+/// the `on_conflict` policy, with `reject` answering 409 Conflict.
+///
+/// Batches larger than the configured limit are rejected as a whole. A batch
+/// for a meter with no registered account is answered with 404 Not Found,
+/// however many of its readings are valid.
 pub async fn create_readings(
+    Query(query): Query<CreateReadingsQueryParams>,
     State(state): State<AppState>,
     Json(body): Json<CreateElectricityReadingsRequest>,
-) -> Result<String, StatusCode> {
-    let smart_meter_id = body.smart_meter_id;
-    let readings = body.electricity_readings;
-    let mut data_store = state.db.lock().unwrap();
+) -> Result<Json<CreateElectricityReadingsResponse>, ApiError> {
+    let max_readings = state.limits.max_readings_per_request;
+    if body.electricity_readings.len() > max_readings {
+        return Err(invalid_readings(vec![FieldError::new(
+            "electricity_readings",
+            format!("must contain at most {max_readings} readings"),
+        )]));
+    }
+    let validated = body
+        .validate(OffsetDateTime::now_utc())
+        .map_err(invalid_readings)?;
+    if state
+        .db
+        .lock()
+        .unwrap()
+        .get_account(&body.smart_meter_id)
+        .is_none()
+    {
+        return Err(DataStoreError::UnknownSmartMeter(body.smart_meter_id).into());
+    }
+    if query.mode == ValidationMode::Strict && !validated.rejected.is_empty() {
+        return Err(invalid_readings(validated.rejected));
+    }
+
+    let accepted = validated.valid.len();
+    let summary = if accepted > 0 {
+        let mut data_store = state.db.lock().unwrap();
+        data_store
+            .insert_readings_with_policy(
+                body.smart_meter_id.clone(),
+                validated.valid,
+                query.on_conflict,
+            )
+            .map_err(|e| match e {
+                DataStoreError::ConflictingReadings(times) => conflicting_readings(&body, &times),
+                e => e.into(),
+            })?
+    } else {
+        InsertSummary::default()
+    };
 
-    let readings = readings
+    Ok(Json(CreateElectricityReadingsResponse {
+        accepted,
+        inserted: summary.inserted,
+        replaced: summary.replaced,
+        skipped: summary.skipped,
+        rejected: validated.rejected,
+    }))
+}
+
+fn conflicting_readings(
+    body: &CreateElectricityReadingsRequest,
+    times: &[OffsetDateTime],
+) -> ApiError {
+    let errors = body
+        .electricity_readings
         .iter()
-        .map(|r| ElectricityReading {
-            reading: r.reading,
-            time: r.time,
+        .enumerate()
+        .filter(|(_, reading)| times.contains(&reading.time))
+        .map(|(index, _)| {
+            FieldError::new(
+                format!("electricity_readings[{index}].time"),
+                "conflicts with a different reading stored for the same time",
+            )
         })
-        .collect::<Vec<ElectricityReading>>();
-    data_store.insert_readings(smart_meter_id, readings);
+        .collect();
+    ApiError::from(DataStoreError::ConflictingReadings(times.to_vec())).with_errors(errors)
+}
 
-    Ok("Readings created successfully".to_string())
+fn invalid_readings(errors: Vec<FieldError>) -> ApiError {
+    ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "invalid-readings",
+        "the request contains invalid readings",
+    )
+    .with_errors(errors)
 }
 
 #[cfg(test)]
 mod tests {
-    use axum::extract::{Path, State};
-    use axum::Json;
+    use axum::extract::State;
+    use axum::http::StatusCode;
     use time::macros::datetime;
 
+    use crate::config::RequestLimits;
     use crate::datastore::reading::ElectricityReading;
     use crate::datastore::state::AppState;
+    use crate::datastore::store::ConflictPolicy;
+    use crate::handlers::extract::{Json, Path, Query};
     use crate::handlers::readings::{create_readings, get_readings};
+    use crate::models::error::FieldError;
     use crate::models::readings::{
-        CreateElectricityReadingsRequest, GetElectricityReadingRequest,
-        GetElectricityReadingResponse,
+        CreateElectricityReadingsRequest, CreateElectricityReadingsResponse,
+        CreateReadingsQueryParams, GetElectricityReadingRequest, GetElectricityReadingResponse,
+        GetReadingsQueryParams,
     };
+    use crate::models::validation::ValidationMode;
 
     fn make_state() -> AppState {
         AppState::default()
@@ -63,9 +192,11 @@ mod tests {
     async fn testing_getting_empty_readings() {
         let state = make_state();
         let path = Path("smart-meter-0".to_string());
-        let Json(result) = get_readings(path, State(state)).await.unwrap();
+        let query = Query(GetReadingsQueryParams::default());
+        let Json(result) = get_readings(path, query, State(state)).await.unwrap();
 
-        assert_eq!(Vec::<GetElectricityReadingResponse>::new(), result);
+        assert_eq!(Vec::<GetElectricityReadingResponse>::new(), result.items);
+        assert_eq!(None, result.next_cursor);
     }
 
     #[tokio::test]
@@ -78,21 +209,36 @@ mod tests {
                 GetElectricityReadingRequest {
                     time: datetime!(2020-11-29 08:00:00 UTC),
                     reading: 1.0,
+                    export: None,
                 },
                 GetElectricityReadingRequest {
                     time: datetime!(2020-11-29 08:01:00 UTC),
                     reading: 2.0,
+                    export: None,
                 },
                 GetElectricityReadingRequest {
                     time: datetime!(2020-11-29 08:02:00 UTC),
                     reading: 3.0,
+                    export: None,
                 },
             ],
         });
 
-        let result = create_readings(State(state), request_body).await.unwrap();
+        let query = Query(CreateReadingsQueryParams::default());
+        let Json(result) = create_readings(query, State(state), request_body)
+            .await
+            .unwrap();
 
-        assert_eq!(result, "Readings created successfully".to_string());
+        assert_eq!(
+            result,
+            CreateElectricityReadingsResponse {
+                accepted: 3,
+                inserted: 3,
+                replaced: 0,
+                skipped: 0,
+                rejected: Vec::new(),
+            }
+        );
     }
 
     #[tokio::test]
@@ -104,35 +250,365 @@ mod tests {
                 ElectricityReading {
                     time: datetime!(2020-11-29 08:00:00 UTC),
                     reading: 1.0,
+                    export: None,
                 },
                 ElectricityReading {
                     time: datetime!(2020-11-29 08:01:00 UTC),
                     reading: 2.0,
+                    export: None,
                 },
                 ElectricityReading {
                     time: datetime!(2020-11-29 08:02:00 UTC),
                     reading: 3.0,
+                    export: None,
                 },
             ];
-            db.insert_readings("smart-meter-0".to_string(), readings);
+            db.insert_readings("smart-meter-0".to_string(), readings)
+                .unwrap();
         }
         let path = Path("smart-meter-0".to_string());
-        let Json(result) = get_readings(path, State(state)).await.unwrap();
+        let query = Query(GetReadingsQueryParams::default());
+        let Json(result) = get_readings(path, query, State(state)).await.unwrap();
 
         let expected_results = vec![
             GetElectricityReadingResponse {
                 time: datetime!(2020-11-29 08:00:00 UTC),
                 reading: 1.0,
+                export: None,
             },
             GetElectricityReadingResponse {
                 time: datetime!(2020-11-29 08:01:00 UTC),
                 reading: 2.0,
+                export: None,
             },
             GetElectricityReadingResponse {
                 time: datetime!(2020-11-29 08:02:00 UTC),
                 reading: 3.0,
+                export: None,
             },
         ];
-        assert_eq!(expected_results, result);
+        assert_eq!(expected_results, result.items);
+    }
+
+    #[tokio::test]
+    async fn testing_getting_readings_in_time_range() {
+        let state = make_state();
+        {
+            let mut db = state.db.lock().unwrap();
+            let readings = vec![
+                ElectricityReading {
+                    time: datetime!(2020-11-29 08:02:00 UTC),
+                    reading: 3.0,
+                    export: None,
+                },
+                ElectricityReading {
+                    time: datetime!(2020-11-29 08:00:00 UTC),
+                    reading: 1.0,
+                    export: None,
+                },
+                ElectricityReading {
+                    time: datetime!(2020-11-29 08:01:00 UTC),
+                    reading: 2.0,
+                    export: None,
+                },
+            ];
+            db.insert_readings("smart-meter-0".to_string(), readings)
+                .unwrap();
+        }
+        let path = Path("smart-meter-0".to_string());
+        let query = Query(GetReadingsQueryParams {
+            from: Some(datetime!(2020-11-29 08:01:00 UTC)),
+            ..Default::default()
+        });
+        let Json(result) = get_readings(path, query, State(state)).await.unwrap();
+
+        let expected_results = vec![
+            GetElectricityReadingResponse {
+                time: datetime!(2020-11-29 08:01:00 UTC),
+                reading: 2.0,
+                export: None,
+            },
+            GetElectricityReadingResponse {
+                time: datetime!(2020-11-29 08:02:00 UTC),
+                reading: 3.0,
+                export: None,
+            },
+        ];
+        assert_eq!(expected_results, result.items);
+    }
+
+    #[tokio::test]
+    async fn testing_getting_readings_with_inverted_range() {
+        let state = make_state();
+        let path = Path("smart-meter-0".to_string());
+        let query = Query(GetReadingsQueryParams {
+            from: Some(datetime!(2020-11-29 09:00:00 UTC)),
+            to: Some(datetime!(2020-11-29 08:00:00 UTC)),
+            ..Default::default()
+        });
+
+        let result = get_readings(path, query, State(state)).await;
+
+        assert_eq!(result.unwrap_err().status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn testing_paging_through_readings() {
+        let state = make_state();
+        {
+            let mut db = state.db.lock().unwrap();
+            let readings = vec![
+                ElectricityReading {
+                    time: datetime!(2020-11-29 08:00:00 UTC),
+                    reading: 1.0,
+                    export: None,
+                },
+                ElectricityReading {
+                    time: datetime!(2020-11-29 08:01:00 UTC),
+                    reading: 2.0,
+                    export: None,
+                },
+                ElectricityReading {
+                    time: datetime!(2020-11-29 08:02:00 UTC),
+                    reading: 3.0,
+                    export: None,
+                },
+            ];
+            db.insert_readings("smart-meter-0".to_string(), readings)
+                .unwrap();
+        }
+
+        let path = Path("smart-meter-0".to_string());
+        let query = Query(GetReadingsQueryParams {
+            limit: Some(2),
+            ..Default::default()
+        });
+        let Json(first_page) = get_readings(path, query, State(state.clone()))
+            .await
+            .unwrap();
+
+        assert_eq!(first_page.items.len(), 2);
+        assert_eq!(first_page.limit, 2);
+        assert!(first_page.next_cursor.is_some());
+
+        let path = Path("smart-meter-0".to_string());
+        let query = Query(GetReadingsQueryParams {
+            limit: Some(2),
+            cursor: first_page.next_cursor,
+            ..Default::default()
+        });
+        let Json(second_page) = get_readings(path, query, State(state)).await.unwrap();
+
+        assert_eq!(
+            second_page.items,
+            vec![GetElectricityReadingResponse {
+                time: datetime!(2020-11-29 08:02:00 UTC),
+                reading: 3.0,
+                export: None,
+            }]
+        );
+        assert_eq!(second_page.next_cursor, None);
+    }
+
+    #[tokio::test]
+    async fn testing_getting_readings_with_invalid_cursor() {
+        let state = make_state();
+        let path = Path("smart-meter-0".to_string());
+        let query = Query(GetReadingsQueryParams {
+            cursor: Some("not-a-cursor".to_string()),
+            ..Default::default()
+        });
+
+        let result = get_readings(path, query, State(state)).await;
+
+        assert_eq!(result.unwrap_err().status, StatusCode::BAD_REQUEST);
+    }
+
+    fn request_with_invalid_reading() -> Json<CreateElectricityReadingsRequest> {
+        Json(CreateElectricityReadingsRequest {
+            smart_meter_id: "smart-meter-0".to_string(),
+            electricity_readings: vec![
+                GetElectricityReadingRequest {
+                    time: datetime!(2020-11-29 08:00:00 UTC),
+                    reading: 1.0,
+                    export: None,
+                },
+                GetElectricityReadingRequest {
+                    time: datetime!(2020-11-29 08:01:00 UTC),
+                    reading: -2.0,
+                    export: None,
+                },
+            ],
+        })
+    }
+
+    #[tokio::test]
+    async fn testing_strict_mode_rejects_batch_with_invalid_reading() {
+        let state = make_state();
+        let query = Query(CreateReadingsQueryParams::default());
+
+        let error = create_readings(query, State(state.clone()), request_with_invalid_reading())
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            error.errors,
+            vec![FieldError::new(
+                "electricity_readings[1].reading",
+                "must not be negative"
+            )]
+        );
+        let db = state.db.lock().unwrap();
+        assert!(db.get_readings(&"smart-meter-0".to_string()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn testing_lenient_mode_drops_invalid_readings() {
+        let state = make_state();
+        let query = Query(CreateReadingsQueryParams {
+            mode: ValidationMode::Lenient,
+            ..Default::default()
+        });
+
+        let Json(result) =
+            create_readings(query, State(state.clone()), request_with_invalid_reading())
+                .await
+                .unwrap();
+
+        assert_eq!(result.accepted, 1);
+        assert_eq!(result.rejected.len(), 1);
+        let db = state.db.lock().unwrap();
+        assert_eq!(
+            db.get_readings(&"smart-meter-0".to_string()),
+            vec![ElectricityReading {
+                time: datetime!(2020-11-29 08:00:00 UTC),
+                reading: 1.0,
+                export: None,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn testing_readings_for_unknown_meter_are_not_found() {
+        let state = make_state();
+        let query = Query(CreateReadingsQueryParams {
+            mode: ValidationMode::Lenient,
+            ..Default::default()
+        });
+        let request = Json(CreateElectricityReadingsRequest {
+            smart_meter_id: "smart-meter-unknown".to_string(),
+            electricity_readings: vec![GetElectricityReadingRequest {
+                time: datetime!(2020-11-29 08:00:00 UTC),
+                reading: -1.0,
+                export: None,
+            }],
+        });
+
+        let error = create_readings(query, State(state), request)
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn testing_oversized_batch_is_rejected() {
+        let state = make_state().with_limits(RequestLimits {
+            max_readings_per_request: 1,
+            ..Default::default()
+        });
+        let query = Query(CreateReadingsQueryParams {
+            mode: ValidationMode::Lenient,
+            ..Default::default()
+        });
+
+        let error = create_readings(query, State(state), request_with_invalid_reading())
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            error.errors,
+            vec![FieldError::new(
+                "electricity_readings",
+                "must contain at most 1 readings"
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn testing_retrying_an_upload_skips_stored_readings() {
+        let state = make_state();
+        let request = || {
+            Json(CreateElectricityReadingsRequest {
+                smart_meter_id: "smart-meter-0".to_string(),
+                electricity_readings: vec![GetElectricityReadingRequest {
+                    time: datetime!(2020-11-29 08:00:00 UTC),
+                    reading: 1.0,
+                    export: None,
+                }],
+            })
+        };
+        let query = || Query(CreateReadingsQueryParams::default());
+        create_readings(query(), State(state.clone()), request())
+            .await
+            .unwrap();
+
+        let Json(result) = create_readings(query(), State(state.clone()), request())
+            .await
+            .unwrap();
+
+        assert_eq!(result.inserted, 0);
+        assert_eq!(result.skipped, 1);
+        let db = state.db.lock().unwrap();
+        assert_eq!(db.get_readings(&"smart-meter-0".to_string()).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn testing_rejecting_conflicting_readings() {
+        let state = make_state();
+        {
+            let mut db = state.db.lock().unwrap();
+            let readings = vec![ElectricityReading {
+                time: datetime!(2020-11-29 08:01:00 UTC),
+                reading: 5.0,
+                export: None,
+            }];
+            db.insert_readings("smart-meter-0".to_string(), readings)
+                .unwrap();
+        }
+        let query = Query(CreateReadingsQueryParams {
+            on_conflict: ConflictPolicy::Reject,
+            ..Default::default()
+        });
+        let request = Json(CreateElectricityReadingsRequest {
+            smart_meter_id: "smart-meter-0".to_string(),
+            electricity_readings: vec![
+                GetElectricityReadingRequest {
+                    time: datetime!(2020-11-29 08:00:00 UTC),
+                    reading: 1.0,
+                    export: None,
+                },
+                GetElectricityReadingRequest {
+                    time: datetime!(2020-11-29 08:01:00 UTC),
+                    reading: 2.0,
+                    export: None,
+                },
+            ],
+        });
+
+        let error = create_readings(query, State(state), request)
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.status, StatusCode::CONFLICT);
+        assert_eq!(
+            error.errors,
+            vec![FieldError::new(
+                "electricity_readings[1].time",
+                "conflicts with a different reading stored for the same time"
+            )]
+        );
     }
 }
@@ -0,0 +1,220 @@
+use crate::invoice::{
+    line_description, line_quantity, long_date, plan_details, vat_percent, Invoice,
+};
+use std::fmt::{self, Write};
+
+const CHART_WIDTH: f64 = 620.0;
+const CHART_HEIGHT: f64 = 160.0;
+
+const STYLE: &str = "body { font-family: Helvetica, Arial, sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 1.5em; }
+th, td { padding: 0.25em 0.75em; text-align: left; }
+td.amount, th.amount { text-align: right; }
+tfoot td { border-top: 1px solid #999; }
+.total td { font-weight: bold; }
+svg rect { fill: #3a7bd5; }";
+
+/// Renders an invoice as a self-contained HTML page
+///
+/// The page has no scripts or external resources, and the usage chart is an
+/// inline SVG, so it displays the same offline as it does online.
+pub fn render(invoice: &Invoice) -> String {
+    let mut html = String::new();
+    write_invoice(&mut html, invoice).expect("writing to a String cannot fail");
+    html
+}
+
+fn write_invoice(html: &mut String, invoice: &Invoice) -> fmt::Result {
+    let bill = &invoice.bill;
+    writeln!(html, "<!DOCTYPE html>")?;
+    writeln!(html, "<html lang=\"en\">")?;
+    writeln!(html, "<head>")?;
+    writeln!(html, "<meta charset=\"utf-8\">")?;
+    writeln!(
+        html,
+        "<title>Invoice for {} {}</title>",
+        escape(&invoice.smart_meter_id),
+        bill.period
+    )?;
+    writeln!(html, "<style>\n{STYLE}\n</style>")?;
+    writeln!(html, "</head>")?;
+    writeln!(html, "<body>")?;
+
+    writeln!(html, "<h1>Invoice</h1>")?;
+    writeln!(html, "<p class=\"customer\">{}<br>", escape(&invoice.user))?;
+    writeln!(html, "Smart meter {}<br>", escape(&invoice.smart_meter_id))?;
+    writeln!(
+        html,
+        "Billing period {}: {}</p>",
+        bill.period,
+        invoice.dates()
+    )?;
+
+    writeln!(html, "<h2>Charges</h2>")?;
+    writeln!(html, "<table class=\"lines\">")?;
+    writeln!(
+        html,
+        "<thead><tr><th>Plan</th><th>Description</th><th>Quantity</th><th class=\"amount\">Amount ({})</th></tr></thead>",
+        bill.currency
+    )?;
+    writeln!(html, "<tbody>")?;
+    for line in &bill.lines {
+        writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td class=\"amount\">{}</td></tr>",
+            escape(invoice.plan_name(&line.price_plan_id)),
+            escape(&line_description(line)),
+            line_quantity(line),
+            line.amount
+        )?;
+    }
+    writeln!(html, "</tbody>")?;
+    writeln!(html, "<tfoot>")?;
+    total_row(html, "Subtotal", &bill.subtotal.to_string())?;
+    for tax in &bill.taxes {
+        total_row(
+            html,
+            &format!(
+                "VAT at {} on {} for {}",
+                vat_percent(tax.rate),
+                tax.taxable,
+                escape(invoice.plan_name(&tax.price_plan_id))
+            ),
+            &tax.amount.to_string(),
+        )?;
+    }
+    writeln!(
+        html,
+        "<tr class=\"total\"><td colspan=\"3\">Total</td><td class=\"amount\">{}</td></tr>",
+        bill.total
+    )?;
+    writeln!(html, "</tfoot>")?;
+    writeln!(html, "</table>")?;
+
+    writeln!(html, "<h2>Your usage</h2>")?;
+    writeln!(
+        html,
+        "<p>{:.2} kWh used and {:.2} kWh exported.</p>",
+        bill.consumption_kwh, bill.export_kwh
+    )?;
+    write_chart(html, invoice)?;
+    writeln!(html, "<table class=\"usage\">")?;
+    writeln!(
+        html,
+        "<thead><tr><th>Date</th><th class=\"amount\">Used (kWh)</th><th class=\"amount\">Exported (kWh)</th></tr></thead>"
+    )?;
+    writeln!(html, "<tbody>")?;
+    for day in &invoice.daily_usage {
+        writeln!(
+            html,
+            "<tr><td>{}</td><td class=\"amount\">{:.2}</td><td class=\"amount\">{:.2}</td></tr>",
+            long_date(day.date),
+            day.consumption_kwh,
+            day.export_kwh
+        )?;
+    }
+    writeln!(html, "</tbody>")?;
+    writeln!(html, "</table>")?;
+
+    writeln!(html, "<h2>Your plan</h2>")?;
+    for rates in &invoice.price_plans {
+        let price_plan = &rates.price_plan;
+        writeln!(
+            html,
+            "<h3>{} ({})</h3>",
+            escape(&price_plan.name),
+            escape(&price_plan.id)
+        )?;
+        writeln!(html, "<p>{}</p>", escape(&rates.dates()))?;
+        writeln!(html, "<table class=\"plan\">")?;
+        for (label, value) in plan_details(price_plan) {
+            writeln!(
+                html,
+                "<tr><th>{}</th><td>{}</td></tr>",
+                escape(&label),
+                escape(&value)
+            )?;
+        }
+        writeln!(html, "</table>")?;
+    }
+
+    writeln!(html, "</body>")?;
+    writeln!(html, "</html>")
+}
+
+fn total_row(html: &mut String, label: &str, amount: &str) -> fmt::Result {
+    writeln!(
+        html,
+        "<tr><td colspan=\"3\">{label}</td><td class=\"amount\">{amount}</td></tr>"
+    )
+}
+
+/// Draws the energy used each day as a bar chart, each bar titled with its
+/// date and kWh
+fn write_chart(html: &mut String, invoice: &Invoice) -> fmt::Result {
+    let days = invoice.daily_usage.len().max(1) as f64;
+    let peak_kwh = invoice
+        .daily_usage
+        .iter()
+        .map(|day| day.consumption_kwh)
+        .fold(0.0, f64::max);
+    let bar_width = CHART_WIDTH / days;
+    writeln!(
+        html,
+        "<svg class=\"chart\" width=\"{CHART_WIDTH}\" height=\"{CHART_HEIGHT}\" viewBox=\"0 0 {CHART_WIDTH} {CHART_HEIGHT}\" role=\"img\" aria-label=\"Energy used each day\">"
+    )?;
+    for (index, day) in invoice.daily_usage.iter().enumerate() {
+        let height = if peak_kwh > 0.0 {
+            day.consumption_kwh / peak_kwh * CHART_HEIGHT
+        } else {
+            0.0
+        };
+        writeln!(
+            html,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{height:.1}\"><title>{}: {:.2} kWh</title></rect>",
+            index as f64 * bar_width,
+            CHART_HEIGHT - height,
+            bar_width * 0.8,
+            long_date(day.date),
+            day.consumption_kwh
+        )?;
+    }
+    writeln!(html, "</svg>")
+}
+
+/// Escapes text for use in HTML content and attribute values
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::invoice::tests::invoice;
+
+    #[test]
+    fn test_render_shows_bill_and_plan() {
+        let html = render(&invoice());
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<p class=\"customer\">Sarah &lt;O&#39;Neill&gt; &amp; Co<br>"));
+        assert!(html.contains("Smart meter smart-meter-0<br>"));
+        assert!(html.contains("Billing period 2024-02: 1 February 2024 to 29 February 2024"));
+        assert!(html.contains(
+            "<tr><td>Economy 7</td><td>Energy used (off-peak)</td><td>14.00 kWh</td><td class=\"amount\">1.40</td></tr>"
+        ));
+        assert!(html.contains("<th>off-peak (00:00 to 07:00)</th><td>0.1 GBP/kWh</td>"));
+        assert_eq!(html.matches("<rect ").count(), 29);
+    }
+}
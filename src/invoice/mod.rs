@@ -0,0 +1,352 @@
+//! Customer-facing invoices for a bill, rendered as HTML or PDF without any
+//! external service.
+
+pub mod html;
+pub mod pdf;
+
+use crate::datastore::account::Account;
+use crate::datastore::bill::{Bill, BillLine, LineKind};
+use crate::datastore::catalogue::PricePlanDefinition;
+use crate::datastore::consumption::Consumption;
+use crate::datastore::plan::PricePlan;
+use time::macros::format_description;
+use time::{Date, Duration, OffsetDateTime};
+
+/// Everything an invoice shows: who it is for, the bill, the meter's daily
+/// usage over the month and the plans it was billed on
+#[derive(Clone, Debug, PartialEq)]
+pub struct Invoice {
+    pub user: String,
+    pub smart_meter_id: String,
+    pub bill: Bill,
+    /// The energy used and exported on each day of the month, for the chart
+    pub daily_usage: Vec<DailyUsage>,
+    /// The rates of the plans the bill's lines were charged on, in the order
+    /// the plans appear, with one entry for each version of a plan's rates in
+    /// force during the month
+    pub price_plans: Vec<PlanRates>,
+}
+
+/// A plan as it stood for part of a billing period
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlanRates {
+    /// The plan, with the rates in force from `from` until `to`
+    pub price_plan: PricePlanDefinition,
+    pub from: OffsetDateTime,
+    pub to: OffsetDateTime,
+}
+
+/// The energy a meter used and exported over one day
+#[derive(Clone, Debug, PartialEq)]
+pub struct DailyUsage {
+    pub date: Date,
+    pub consumption_kwh: f64,
+    pub export_kwh: f64,
+}
+
+impl Invoice {
+    /// Gathers what an invoice shows for a bill
+    ///
+    /// # Arguments
+    /// * `smart_meter_id` - The meter the bill is for
+    /// * `account` - The account the meter is registered to
+    /// * `bill` - The bill to invoice
+    /// * `consumption` - The consumption the bill was generated from
+    /// * `price_plans` - The price plans the bill refers to, whose rates are
+    ///   shown for each version in force during the billing period
+    pub fn new(
+        smart_meter_id: &str,
+        account: &Account,
+        bill: Bill,
+        consumption: &Consumption,
+        price_plans: &[PricePlan],
+    ) -> Self {
+        let mut daily_usage = Vec::new();
+        let mut day = bill.from;
+        while day < bill.to {
+            let used = consumption.between(Some(day), Some(day + Duration::DAY));
+            daily_usage.push(DailyUsage {
+                date: day.date(),
+                consumption_kwh: used.total_kwh(),
+                export_kwh: used.total_export_kwh(),
+            });
+            day += Duration::DAY;
+        }
+
+        let mut billed_plans = Vec::<PlanRates>::new();
+        for line in &bill.lines {
+            if billed_plans
+                .iter()
+                .any(|billed| billed.price_plan.id == line.price_plan_id)
+            {
+                continue;
+            }
+            let Some(price_plan) = price_plans
+                .iter()
+                .find(|price_plan| price_plan.supplier_id == line.price_plan_id)
+            else {
+                continue;
+            };
+            let changes = price_plan.rate_changes_between(bill.from, bill.to);
+            let starts = std::iter::once(bill.from).chain(changes.iter().copied());
+            let ends = changes.iter().copied().chain(std::iter::once(bill.to));
+            billed_plans.extend(starts.zip(ends).map(|(from, to)| PlanRates {
+                price_plan: PricePlanDefinition::from(&price_plan.at_rates_in_force(from)),
+                from: from.to_offset(bill.from.offset()),
+                to: to.to_offset(bill.from.offset()),
+            }));
+        }
+
+        Self {
+            user: account.user.clone(),
+            smart_meter_id: smart_meter_id.to_string(),
+            bill,
+            daily_usage,
+            price_plans: billed_plans,
+        }
+    }
+
+    /// Describes the dates the invoice covers, e.g. `1 January 2024 to 31 January 2024`
+    pub fn dates(&self) -> String {
+        let last_day = (self.bill.to - Duration::DAY).date();
+        format!(
+            "{} to {}",
+            long_date(self.bill.from.date()),
+            long_date(last_day)
+        )
+    }
+
+    /// The name to show for a plan the bill refers to, falling back to its id
+    pub fn plan_name<'a>(&'a self, price_plan_id: &'a str) -> &'a str {
+        self.price_plans
+            .iter()
+            .map(|rates| &rates.price_plan)
+            .find(|price_plan| price_plan.id == price_plan_id)
+            .map_or(price_plan_id, |price_plan| price_plan.name.as_str())
+    }
+}
+
+impl PlanRates {
+    /// Describes the days the rates were in force, e.g. `1 February 2024 to
+    /// 14 February 2024`
+    ///
+    /// A day the rates changed partway through appears under both versions.
+    pub fn dates(&self) -> String {
+        let last_day = (self.to - Duration::NANOSECOND).date();
+        format!("{} to {}", long_date(self.from.date()), long_date(last_day))
+    }
+}
+
+/// Writes a date the way an invoice does, e.g. `1 January 2024`
+pub fn long_date(date: Date) -> String {
+    date.format(format_description!(
+        "[day padding:none] [month repr:long] [year]"
+    ))
+    .expect("a date can always be formatted")
+}
+
+/// Describes a line of a bill, e.g. `Energy used (off-peak)`
+pub fn line_description(line: &BillLine) -> String {
+    match line.kind {
+        LineKind::Consumption => format!("Energy used ({})", line.description),
+        LineKind::StandingCharge => "Standing charge".to_string(),
+        LineKind::ExportCredit => "Energy exported".to_string(),
+    }
+}
+
+/// Writes a line's quantity with its unit, e.g. `12.50 kWh` or `31.00 days`
+pub fn line_quantity(line: &BillLine) -> String {
+    let unit = serde_json::to_value(line.unit).expect("units always serialize");
+    format!("{:.2} {}", line.quantity, unit.as_str().unwrap_or_default())
+}
+
+/// Writes a VAT rate as a percentage, e.g. `5%` or `17.5%`
+pub fn vat_percent(rate: f64) -> String {
+    let percent = format!("{:.2}", rate * 100.0);
+    format!("{}%", percent.trim_end_matches('0').trim_end_matches('.'))
+}
+
+/// Lists a plan's rates as label and value pairs, in the plan's currency
+pub fn plan_details(price_plan: &PricePlanDefinition) -> Vec<(String, String)> {
+    let currency = price_plan.currency;
+    let mut details = vec![
+        (
+            "Unit rate".to_string(),
+            format!("{} {currency}/kWh", price_plan.unit_rate),
+        ),
+        (
+            "Standing charge".to_string(),
+            format!("{} {currency}/day", price_plan.standing_charge),
+        ),
+    ];
+    for band in &price_plan.rate_bands {
+        details.push((
+            format!("{} ({} to {})", band.name, band.start, band.end),
+            format!("{} {currency}/kWh", band.unit_rate),
+        ));
+    }
+    let mut from_kwh = 0.0;
+    for (index, tier) in price_plan.tiers.iter().enumerate() {
+        details.push((
+            format!(
+                "Tier {} ({from_kwh} to {} kWh a month)",
+                index + 1,
+                tier.up_to_kwh
+            ),
+            format!("{} {currency}/kWh", tier.unit_rate),
+        ));
+        from_kwh = tier.up_to_kwh;
+    }
+    for (weekday, multiplier) in &price_plan.multipliers {
+        details.push((format!("{weekday} rates"), format!("x{multiplier}")));
+    }
+    if price_plan.export_rate != 0.0 {
+        details.push((
+            "Export rate".to_string(),
+            format!("{} {currency}/kWh", price_plan.export_rate),
+        ));
+    }
+    let vat_category =
+        serde_json::to_value(price_plan.vat_category).expect("VAT categories always serialize");
+    details.push((
+        "VAT".to_string(),
+        vat_category.as_str().unwrap_or_default().to_string(),
+    ));
+    details
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datastore::bill::BillingPeriod;
+    use crate::datastore::consumption::IntegrationMethod;
+    use crate::datastore::money::Rounding;
+    use crate::datastore::plan::{RateBand, RateChange};
+    use crate::datastore::reading::ElectricityReading;
+    use crate::datastore::tax::VatRates;
+    use std::collections::HashMap;
+    use time::macros::{date, datetime, time};
+    use time::{Month, UtcOffset};
+
+    /// An invoice for Sarah's February 2024 on an Economy 7 plan, with 1 kW
+    /// used through the first two days
+    pub(super) fn invoice() -> Invoice {
+        let account = Account::new("plan-e7", "Sarah <O'Neill> & Co");
+        let price_plans = vec![PricePlan::new("plan-e7", "Economy 7", 0.3, HashMap::new())
+            .with_rate_bands(
+                UtcOffset::UTC,
+                vec![RateBand::new("off-peak", time!(00:00), time!(07:00), 0.1)],
+            )
+            .with_standing_charge(0.48)];
+        let readings = vec![
+            ElectricityReading::new(datetime!(2024-02-01 00:00 UTC), 1.0),
+            ElectricityReading::new(datetime!(2024-02-03 00:00 UTC), 1.0),
+        ];
+        let consumption = Consumption::from_readings(&readings, IntegrationMethod::Trapezoidal);
+        let bill = Bill::generate(
+            &account,
+            &consumption,
+            &price_plans,
+            BillingPeriod::new(2024, Month::February),
+            Rounding::PerLine,
+            &VatRates::default(),
+        )
+        .unwrap();
+        Invoice::new("smart-meter-0", &account, bill, &consumption, &price_plans)
+    }
+
+    #[test]
+    fn test_invoice_has_a_day_of_usage_for_each_day_of_the_month() {
+        let invoice = invoice();
+
+        assert_eq!(invoice.daily_usage.len(), 29);
+        assert_eq!(
+            invoice.daily_usage[1],
+            DailyUsage {
+                date: date!(2024 - 02 - 02),
+                consumption_kwh: 24.0,
+                export_kwh: 0.0,
+            }
+        );
+        assert_eq!(invoice.daily_usage[2].consumption_kwh, 0.0);
+        assert_eq!(invoice.dates(), "1 February 2024 to 29 February 2024");
+        assert_eq!(invoice.plan_name("plan-e7"), "Economy 7");
+    }
+
+    #[test]
+    fn test_plan_details() {
+        let invoice = invoice();
+
+        assert_eq!(
+            plan_details(&invoice.price_plans[0].price_plan),
+            vec![
+                ("Unit rate".to_string(), "0.3 GBP/kWh".to_string()),
+                ("Standing charge".to_string(), "0.48 GBP/day".to_string()),
+                (
+                    "off-peak (00:00 to 07:00)".to_string(),
+                    "0.1 GBP/kWh".to_string()
+                ),
+                ("VAT".to_string(), "domestic".to_string()),
+            ]
+        );
+        assert_eq!(line_quantity(&invoice.bill.lines[0]), "14.00 kWh");
+        assert_eq!(
+            line_description(&invoice.bill.lines[0]),
+            "Energy used (off-peak)"
+        );
+    }
+
+    #[test]
+    fn test_plan_details_show_each_version_of_the_rates_billed() {
+        let account = Account::new("plan-flat", "Sarah");
+        let mut price_plan = PricePlan::new("plan-flat", "Flat", 0.3, HashMap::new());
+        price_plan.change_rates(datetime!(2024-02-15 00:00 UTC), RateChange::new(0.4));
+        price_plan.change_rates(datetime!(2024-03-01 00:00 UTC), RateChange::new(0.5));
+        let price_plans = vec![price_plan];
+        // An hour on each side of the mid-month change
+        let readings = vec![
+            ElectricityReading::new(datetime!(2024-02-14 23:00 UTC), 1.0),
+            ElectricityReading::new(datetime!(2024-02-15 01:00 UTC), 1.0),
+        ];
+        let consumption = Consumption::from_readings(&readings, IntegrationMethod::Trapezoidal);
+        let bill = Bill::generate(
+            &account,
+            &consumption,
+            &price_plans,
+            BillingPeriod::new(2024, Month::February),
+            Rounding::PerLine,
+            &VatRates::default(),
+        )
+        .unwrap();
+
+        let invoice = Invoice::new("smart-meter-0", &account, bill, &consumption, &price_plans);
+        let rates = invoice
+            .price_plans
+            .iter()
+            .map(|rates| (rates.dates(), plan_details(&rates.price_plan)[0].1.clone()))
+            .collect::<Vec<(String, String)>>();
+
+        assert_eq!(invoice.bill.lines[0].amount.to_f64(), 0.7);
+        assert_eq!(
+            rates,
+            vec![
+                (
+                    "1 February 2024 to 14 February 2024".to_string(),
+                    "0.3 GBP/kWh".to_string()
+                ),
+                (
+                    "15 February 2024 to 29 February 2024".to_string(),
+                    "0.4 GBP/kWh".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_vat_percent() {
+        assert_eq!(vat_percent(0.05), "5%");
+        assert_eq!(vat_percent(0.07), "7%");
+        assert_eq!(vat_percent(0.175), "17.5%");
+        assert_eq!(vat_percent(0.0), "0%");
+    }
+}
@@ -0,0 +1,370 @@
+use crate::invoice::{line_description, line_quantity, plan_details, vat_percent, Invoice};
+
+/// A4 portrait, in points
+const PAGE_WIDTH: f64 = 595.0;
+const PAGE_HEIGHT: f64 = 842.0;
+const MARGIN: f64 = 50.0;
+const CHART_HEIGHT: f64 = 120.0;
+
+/// Renders an invoice as a PDF document
+///
+/// The document is written directly, using the standard Helvetica fonts every
+/// PDF reader has, so nothing needs to be embedded or fetched. Text outside
+/// the Windows-1252 character set is replaced with `?`.
+pub fn render(invoice: &Invoice) -> Vec<u8> {
+    let bill = &invoice.bill;
+    let mut pages = Pages::new();
+
+    pages.text(MARGIN, 20.0, Font::Bold, "Invoice");
+    pages.gap(8.0);
+    pages.text(MARGIN, 11.0, Font::Regular, &invoice.user);
+    pages.text(
+        MARGIN,
+        11.0,
+        Font::Regular,
+        &format!("Smart meter {}", invoice.smart_meter_id),
+    );
+    pages.text(
+        MARGIN,
+        11.0,
+        Font::Regular,
+        &format!("Billing period {}: {}", bill.period, invoice.dates()),
+    );
+
+    pages.gap(12.0);
+    pages.text(MARGIN, 14.0, Font::Bold, "Charges");
+    pages.row(
+        Font::Bold,
+        &[
+            "Plan",
+            "Description",
+            "Quantity",
+            &format!("Amount ({})", bill.currency),
+        ],
+    );
+    for line in &bill.lines {
+        pages.row(
+            Font::Regular,
+            &[
+                invoice.plan_name(&line.price_plan_id),
+                &line_description(line),
+                &line_quantity(line),
+                &line.amount.to_string(),
+            ],
+        );
+    }
+    pages.rule();
+    pages.row(
+        Font::Regular,
+        &["Subtotal", "", "", &bill.subtotal.to_string()],
+    );
+    for tax in &bill.taxes {
+        pages.row(
+            Font::Regular,
+            &[
+                &format!("VAT at {}", vat_percent(tax.rate)),
+                &format!(
+                    "on {} for {}",
+                    tax.taxable,
+                    invoice.plan_name(&tax.price_plan_id)
+                ),
+                "",
+                &tax.amount.to_string(),
+            ],
+        );
+    }
+    pages.row(Font::Bold, &["Total", "", "", &bill.total.to_string()]);
+
+    pages.gap(12.0);
+    pages.text(MARGIN, 14.0, Font::Bold, "Your usage");
+    pages.text(
+        MARGIN,
+        10.0,
+        Font::Regular,
+        &format!(
+            "{:.2} kWh used and {:.2} kWh exported.",
+            bill.consumption_kwh, bill.export_kwh
+        ),
+    );
+    pages.chart(
+        &invoice
+            .daily_usage
+            .iter()
+            .map(|day| day.consumption_kwh)
+            .collect::<Vec<f64>>(),
+    );
+
+    pages.gap(12.0);
+    pages.text(MARGIN, 14.0, Font::Bold, "Your plan");
+    for rates in &invoice.price_plans {
+        let price_plan = &rates.price_plan;
+        pages.text(
+            MARGIN,
+            11.0,
+            Font::Bold,
+            &format!("{} ({})", price_plan.name, price_plan.id),
+        );
+        pages.text(MARGIN, 10.0, Font::Regular, &rates.dates());
+        for (label, value) in plan_details(price_plan) {
+            pages.row(Font::Regular, &[&label, "", &value, ""]);
+        }
+    }
+
+    write_document(&pages.finish())
+}
+
+#[derive(Clone, Copy)]
+enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    fn resource(self) -> &'static str {
+        match self {
+            Self::Regular => "/F1",
+            Self::Bold => "/F2",
+        }
+    }
+}
+
+/// Lays content out top to bottom, starting a new page when one is full
+struct Pages {
+    /// The content stream of every page, the last one being written to
+    streams: Vec<Vec<u8>>,
+    /// Where the next line's baseline goes, in points from the bottom of the page
+    cursor: f64,
+}
+
+impl Pages {
+    /// Left edges of the columns of a table row
+    const COLUMNS: [f64; 4] = [MARGIN, 170.0, 330.0, 450.0];
+
+    fn new() -> Self {
+        Self {
+            streams: vec![Vec::new()],
+            cursor: PAGE_HEIGHT - MARGIN,
+        }
+    }
+
+    /// Moves down by `height`, first starting a new page if it would not fit
+    fn advance(&mut self, height: f64) -> f64 {
+        if self.cursor - height < MARGIN {
+            self.streams.push(Vec::new());
+            self.cursor = PAGE_HEIGHT - MARGIN;
+        }
+        self.cursor -= height;
+        self.cursor
+    }
+
+    fn gap(&mut self, height: f64) {
+        self.cursor -= height;
+    }
+
+    fn text(&mut self, x: f64, size: f64, font: Font, text: &str) {
+        let y = self.advance(size * 1.4);
+        self.draw_text(x, y, size, font, text);
+    }
+
+    fn row(&mut self, font: Font, cells: &[&str]) {
+        let y = self.advance(14.0);
+        for (x, cell) in Self::COLUMNS.iter().zip(cells) {
+            if !cell.is_empty() {
+                self.draw_text(*x, y, 10.0, font, cell);
+            }
+        }
+    }
+
+    /// Draws a horizontal line across the page, below the last row
+    fn rule(&mut self) {
+        let y = self.cursor - 4.0;
+        self.push(format!(
+            "{MARGIN} {y:.1} m {:.1} {y:.1} l S\n",
+            PAGE_WIDTH - MARGIN
+        ));
+        self.cursor -= 4.0;
+    }
+
+    /// Draws a bar for each value, scaled so the largest fills the chart
+    fn chart(&mut self, values: &[f64]) {
+        let bottom = self.advance(CHART_HEIGHT + 10.0);
+        let peak = values.iter().copied().fold(0.0, f64::max);
+        let bar_width = (PAGE_WIDTH - 2.0 * MARGIN) / values.len().max(1) as f64;
+        self.push("0.23 0.48 0.84 rg\n".to_string());
+        for (index, value) in values.iter().enumerate() {
+            if peak > 0.0 && *value > 0.0 {
+                self.push(format!(
+                    "{:.1} {bottom:.1} {:.1} {:.1} re f\n",
+                    MARGIN + index as f64 * bar_width,
+                    bar_width * 0.8,
+                    value / peak * CHART_HEIGHT
+                ));
+            }
+        }
+        self.push("0 g\n".to_string());
+        self.push(format!(
+            "{MARGIN} {bottom:.1} m {:.1} {bottom:.1} l S\n",
+            PAGE_WIDTH - MARGIN
+        ));
+        self.draw_text(
+            MARGIN,
+            bottom + CHART_HEIGHT + 2.0,
+            8.0,
+            Font::Regular,
+            &format!("{peak:.2} kWh a day at most"),
+        );
+    }
+
+    fn draw_text(&mut self, x: f64, y: f64, size: f64, font: Font, text: &str) {
+        let stream = self.streams.last_mut().expect("there is always a page");
+        stream.extend_from_slice(
+            format!("BT {} {size} Tf {x:.1} {y:.1} Td (", font.resource()).as_bytes(),
+        );
+        stream.extend(encode(text));
+        stream.extend_from_slice(b") Tj ET\n");
+    }
+
+    fn push(&mut self, operators: String) {
+        self.streams
+            .last_mut()
+            .expect("there is always a page")
+            .extend_from_slice(operators.as_bytes());
+    }
+
+    fn finish(self) -> Vec<Vec<u8>> {
+        self.streams
+    }
+}
+
+/// Encodes text as the body of a PDF string in the fonts' WinAnsi encoding
+fn encode(text: &str) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        let byte = match c {
+            '€' => 0x80,
+            '\u{20}'..='\u{7e}' | '\u{a0}'..='\u{ff}' => c as u8,
+            _ => b'?',
+        };
+        if matches!(byte, b'(' | b')' | b'\\') {
+            encoded.push(b'\\');
+        }
+        encoded.push(byte);
+    }
+    encoded
+}
+
+/// Writes the objects of a document with the given page content streams,
+/// followed by the cross-reference table locating each of them
+fn write_document(streams: &[Vec<u8>]) -> Vec<u8> {
+    // The catalog, page tree and two fonts come first, then a page and its
+    // content stream for each page
+    let page_ids = (0..streams.len())
+        .map(|index| 5 + 2 * index)
+        .collect::<Vec<usize>>();
+    let mut objects: Vec<Vec<u8>> = vec![
+        b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            page_ids
+                .iter()
+                .map(|id| format!("{id} 0 R"))
+                .collect::<Vec<String>>()
+                .join(" "),
+            streams.len()
+        )
+        .into_bytes(),
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+            .to_vec(),
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>"
+            .to_vec(),
+    ];
+    for (page_id, stream) in page_ids.iter().zip(streams) {
+        objects.push(
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {PAGE_WIDTH} {PAGE_HEIGHT}] \
+                 /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+                page_id + 1
+            )
+            .into_bytes(),
+        );
+        let mut content = format!("<< /Length {} >>\nstream\n", stream.len()).into_bytes();
+        content.extend_from_slice(stream);
+        content.extend_from_slice(b"\nendstream");
+        objects.push(content);
+    }
+
+    let mut document = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(document.len());
+        document.extend_from_slice(format!("{} 0 obj\n", index + 1).as_bytes());
+        document.extend_from_slice(object);
+        document.extend_from_slice(b"\nendobj\n");
+    }
+    let xref_offset = document.len();
+    document.extend_from_slice(format!("xref\n0 {}\n", objects.len() + 1).as_bytes());
+    document.extend_from_slice(b"0000000000 65535 f \n");
+    for offset in offsets {
+        document.extend_from_slice(format!("{offset:010} 00000 n \n").as_bytes());
+    }
+    document.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref_offset}\n%%EOF\n",
+            objects.len() + 1
+        )
+        .as_bytes(),
+    );
+    document
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::invoice::tests::invoice;
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack
+            .windows(needle.len())
+            .any(|window| window == needle)
+    }
+
+    #[test]
+    fn test_render_writes_a_pdf() {
+        let pdf = render(&invoice());
+
+        assert!(pdf.starts_with(b"%PDF-1.4\n"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+        assert!(contains(&pdf, b"(Sarah <O'Neill> & Co) Tj"));
+        assert!(contains(&pdf, b"(Energy used \\(off-peak\\)) Tj"));
+        assert!(contains(&pdf, b"/Count 1"));
+    }
+
+    #[test]
+    fn test_cross_references_locate_objects() {
+        let pdf = render(&invoice());
+        let text = String::from_utf8_lossy(&pdf);
+        let xref = text.rfind("\nxref\n").unwrap() + 1;
+        let entries = text[xref..]
+            .lines()
+            .skip(3)
+            .take_while(|line| line.ends_with(" n "))
+            .map(|line| line[..10].parse::<usize>().unwrap())
+            .collect::<Vec<usize>>();
+
+        assert_eq!(entries.len(), 6);
+        for (index, offset) in entries.into_iter().enumerate() {
+            assert!(pdf[offset..].starts_with(format!("{} 0 obj", index + 1).as_bytes()));
+        }
+    }
+
+    #[test]
+    fn test_long_content_continues_on_a_new_page() {
+        let mut pages = Pages::new();
+        for _ in 0..100 {
+            pages.row(Font::Regular, &["line"]);
+        }
+
+        assert_eq!(pages.finish().len(), 2);
+        assert_eq!(encode("£5 (€6) 日"), b"\xa35 \\(\x806\\) ?".to_vec());
+    }
+}
@@ -1,4 +1,6 @@
+pub mod config;
 pub mod datastore;
 pub mod handlers;
+pub mod invoice;
 pub mod models;
 pub mod routes;
@@ -1,10 +1,61 @@
+use developer_joyofenergy_rust::config::{CatalogueSource, Config, ConfigError, USAGE};
+use developer_joyofenergy_rust::datastore::catalogue::Catalogue;
+use developer_joyofenergy_rust::datastore::state;
 use developer_joyofenergy_rust::routes::build;
+use std::net::SocketAddr;
+use std::process::ExitCode;
 
 #[tokio::main]
-async fn main() {
-    // Build our application with a single route
-    let app = build().await;
+async fn main() -> ExitCode {
+    let config = match Config::from_process() {
+        Ok(config) => config,
+        Err(ConfigError::HelpRequested) => {
+            print!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:8080").await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    tracing_subscriber::fmt()
+        .with_max_level(config.log_level)
+        .init();
+
+    let catalogue = match &config.catalogue {
+        CatalogueSource::Demo => Ok(Catalogue::demo()),
+        CatalogueSource::File(path) => Catalogue::from_file(path),
+    };
+    let catalogue = match catalogue {
+        Ok(catalogue) => catalogue,
+        Err(e) => {
+            tracing::error!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let state = match state::init(&config, catalogue) {
+        Ok(state) => state,
+        Err(e) => {
+            tracing::error!("failed to open data store: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let app = build(state);
+
+    let address = SocketAddr::new(config.bind_address, config.port);
+    let listener = match tokio::net::TcpListener::bind(address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            tracing::error!("failed to listen on {address}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    tracing::info!("listening on {address}");
+    if let Err(e) = axum::serve(listener, app).await {
+        tracing::error!("server stopped: {e}");
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
 }
@@ -0,0 +1,48 @@
+use crate::datastore::account::{Account, PlanAssignment};
+use crate::datastore::store::SmartMeterId;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CreateAccountRequest {
+    pub smart_meter_id: SmartMeterId,
+    pub user: String,
+    pub price_plan_id: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateAccountRequest {
+    pub user: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SwitchPricePlanRequest {
+    pub price_plan_id: String,
+    /// When the new plan takes effect, now when left out
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub effective_from: Option<OffsetDateTime>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct AccountResponse {
+    pub smart_meter_id: SmartMeterId,
+    pub user: String,
+    /// The plan the account is on now
+    pub price_plan_id: String,
+    pub price_plan_history: Vec<PlanAssignment>,
+}
+
+impl AccountResponse {
+    /// Describes an account as it stands at the given instant
+    pub fn new(smart_meter_id: &SmartMeterId, account: &Account, now: OffsetDateTime) -> Self {
+        Self {
+            smart_meter_id: smart_meter_id.clone(),
+            user: account.user.clone(),
+            price_plan_id: account.price_plan_at(now).to_string(),
+            price_plan_history: account.price_plan_history.clone(),
+        }
+    }
+}
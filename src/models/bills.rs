@@ -0,0 +1,40 @@
+use crate::datastore::bill::{Bill, BillingPeriod};
+use crate::datastore::consumption::IntegrationMethod;
+use crate::datastore::money::Rounding;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Debug)]
+pub struct GetBillQueryParams {
+    pub period: BillingPeriod,
+    #[serde(default)]
+    pub method: IntegrationMethod,
+    #[serde(default)]
+    pub rounding: Rounding,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct GetBillResponse {
+    pub smart_meter_id: String,
+    #[serde(flatten)]
+    pub bill: Bill,
+}
+
+/// The document an invoice is rendered as
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum InvoiceFormat {
+    #[default]
+    Html,
+    Pdf,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GetInvoiceQueryParams {
+    pub period: BillingPeriod,
+    #[serde(default)]
+    pub format: InvoiceFormat,
+    #[serde(default)]
+    pub method: IntegrationMethod,
+    #[serde(default)]
+    pub rounding: Rounding,
+}
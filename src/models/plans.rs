@@ -1,13 +1,160 @@
+use crate::datastore::consumption::IntegrationMethod;
+use crate::datastore::money::{Money, Rounding};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use time::OffsetDateTime;
 
 #[derive(Serialize, Debug, PartialEq)]
 pub struct GetPricePlanCostResponse {
-    pub price_plans: BTreeMap<String, f64>,
+    /// Each plan's average cost per hour, unrounded
+    pub price_plans: BTreeMap<String, Money>,
     pub supplier_id: String,
+    pub consumption_kwh: f64,
+    /// The energy exported to the grid in kWh
+    pub export_kwh: f64,
+    /// What the energy used cost on each plan
+    pub consumption_costs: BTreeMap<String, Money>,
+    /// Each plan's daily standing charge for the time the readings cover
+    pub standing_charges: BTreeMap<String, Money>,
+    /// What each plan pays for the energy exported
+    pub export_credits: BTreeMap<String, Money>,
+    /// The consumption cost plus the standing charge, less the export credit,
+    /// on each plan, rounded as asked, before tax
+    pub total_costs: BTreeMap<String, Money>,
+    /// The VAT each plan charges on its consumption cost and standing charge
+    pub taxes: BTreeMap<String, Money>,
+    /// The total cost on each plan with VAT added
+    pub gross_costs: BTreeMap<String, Money>,
+    /// The cost of the consumption on the plans the account was on at the
+    /// time, the sum of the plan periods' costs before tax
+    ///
+    /// The actual figures are `None` when the account's plans were in
+    /// different currencies.
+    pub actual_cost: Option<Money>,
+    pub actual_tax: Option<Money>,
+    pub actual_gross_cost: Option<Money>,
+    pub plan_periods: Vec<PlanPeriodCost>,
+}
+
+/// The consumption and cost of the time an account spent on one price plan
+#[derive(Serialize, Debug, PartialEq)]
+pub struct PlanPeriodCost {
+    pub price_plan_id: String,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub from: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub to: Option<OffsetDateTime>,
+    pub consumption_kwh: f64,
+    pub consumption_cost: Money,
+    pub standing_charge: Money,
+    pub export_credit: Money,
+    /// The cost before tax
+    pub cost: Money,
+    pub tax: Money,
+    pub gross_cost: Money,
+}
+
+/// A price plan's cost for a meter's usage, as ranked by the recommendations
+#[derive(Serialize, Debug, PartialEq)]
+pub struct PlanRecommendation {
+    pub price_plan_id: String,
+    /// The net cost per hour, standing charge included and export credit
+    /// deducted, unrounded, shown for information
+    pub average_hourly_cost: Money,
+    pub consumption_cost: Money,
+    pub standing_charge: Money,
+    pub export_credit: Money,
+    /// The cost before tax
+    pub total_cost: Money,
+    pub tax: Money,
+    pub gross_cost: Money,
+    /// What a year of usage like the meter's so far would cost, VAT included,
+    /// rounded to the minor unit; plans are ranked by this rounded figure
+    pub projected_annual_cost: Money,
+    /// A twelfth of the projected annual cost
+    pub projected_monthly_cost: Money,
+    /// How much less than on the account's current plan a year would cost,
+    /// negative when the plan costs more, or `None` when the current plan
+    /// cannot be found
+    pub annual_savings: Option<Money>,
+    pub monthly_savings: Option<Money>,
+    /// How much the projections can be trusted, given the history behind them
+    pub confidence: Confidence,
+    /// The days of readings the projections are based on
+    pub history_days: f64,
+}
+
+/// How much a recommendation's projections can be trusted
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Confidence {
+    /// Less than a month of readings
+    Low,
+    /// At least a month of readings, but less than a year, so seasonal
+    /// changes in usage may be missed
+    Medium,
+    /// A year of readings or more
+    High,
+}
+
+impl Confidence {
+    pub fn for_history(days: f64) -> Self {
+        if days >= 365.0 {
+            Self::High
+        } else if days >= 30.0 {
+            Self::Medium
+        } else {
+            Self::Low
+        }
+    }
+}
+
+/// Which of a plan's rates to price consumption at
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RateBasis {
+    /// Each part of the consumption at the rates in force at the time
+    #[default]
+    Historical,
+    /// All of the consumption at the rates in force now
+    Current,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct GetPricePlanCostQueryParams {
+    #[serde(default)]
+    pub method: IntegrationMethod,
+    #[serde(default)]
+    pub rates: RateBasis,
+    #[serde(default)]
+    pub rounding: Rounding,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct GetRecommendationQueryParams {
     pub limit: u64,
+    #[serde(default)]
+    pub method: IntegrationMethod,
+    /// Recommendations look forward, so they use today's rates unless asked
+    #[serde(default = "current_rates")]
+    pub rates: RateBasis,
+    #[serde(default)]
+    pub rounding: Rounding,
+}
+
+fn current_rates() -> RateBasis {
+    RateBasis::Current
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_confidence_grows_with_history() {
+        assert_eq!(Confidence::for_history(0.0), Confidence::Low);
+        assert_eq!(Confidence::for_history(29.9), Confidence::Low);
+        assert_eq!(Confidence::for_history(30.0), Confidence::Medium);
+        assert_eq!(Confidence::for_history(365.0), Confidence::High);
+    }
 }
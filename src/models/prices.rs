@@ -0,0 +1,189 @@
+use crate::datastore::plan::HalfHourPrice;
+use crate::models::error::FieldError;
+use serde::{Deserialize, Serialize};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+/// The header a CSV upload of half-hourly prices must start with
+pub const CSV_HEADER: &str = "start,unit_rate";
+
+/// Half-hourly prices for an agile plan, uploaded as JSON or CSV
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct UploadPricesRequest {
+    pub prices: Vec<HalfHourPrice>,
+}
+
+impl UploadPricesRequest {
+    /// Reads prices from CSV, with a `start,unit_rate` header followed by one
+    /// half hour per line
+    ///
+    /// # Returns
+    /// The prices, or a problem for every line that cannot be read. Lines are
+    /// named like the JSON fields they stand for, so the first price after
+    /// the header is `prices[0]`.
+    pub fn from_csv(text: &str) -> Result<Self, Vec<FieldError>> {
+        let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+        let header = lines.next().unwrap_or_default();
+        if !header.trim().eq_ignore_ascii_case(CSV_HEADER) {
+            return Err(vec![FieldError::new(
+                "header",
+                format!("must be {CSV_HEADER}"),
+            )]);
+        }
+
+        let mut prices = Vec::new();
+        let mut errors = Vec::new();
+        for (index, line) in lines.enumerate() {
+            let at = format!("prices[{index}]");
+            let columns = line.split(',').map(str::trim).collect::<Vec<&str>>();
+            let [start, unit_rate] = columns[..] else {
+                errors.push(FieldError::new(at, "must have a start and a unit_rate"));
+                continue;
+            };
+            let start = OffsetDateTime::parse(start, &Rfc3339).map_err(|_| {
+                FieldError::new(format!("{at}.start"), "must be an RFC 3339 timestamp")
+            });
+            let unit_rate = unit_rate
+                .parse::<f64>()
+                .map_err(|_| FieldError::new(format!("{at}.unit_rate"), "must be a number"));
+            match (start, unit_rate) {
+                (Ok(start), Ok(unit_rate)) => prices.push(HalfHourPrice::new(start, unit_rate)),
+                (start, unit_rate) => errors.extend(start.err().into_iter().chain(unit_rate.err())),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(Self { prices })
+    }
+
+    /// Checks that every price starts on the hour or half past, ends at a
+    /// representable instant and is a finite number
+    ///
+    /// Prices may be negative, as agile tariffs pay customers to use energy
+    /// when there is too much of it.
+    pub fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        for (index, price) in self.prices.iter().enumerate() {
+            if !price.is_aligned() {
+                errors.push(FieldError::new(
+                    format!("prices[{index}].start"),
+                    "must be on the hour or half past",
+                ));
+            } else if price.end().is_none() {
+                errors.push(FieldError::new(
+                    format!("prices[{index}].start"),
+                    "must be followed by a half hour that can be represented",
+                ));
+            }
+            if !price.unit_rate.is_finite() {
+                errors.push(FieldError::new(
+                    format!("prices[{index}].unit_rate"),
+                    "must be a finite number",
+                ));
+            }
+        }
+        errors
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct UploadPricesResponse {
+    /// How many half hours were priced by the upload
+    pub stored: usize,
+    /// The start of the first half hour priced
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub from: Option<OffsetDateTime>,
+    /// The end of the last half hour priced
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub to: Option<OffsetDateTime>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct GetPricesQueryParams {
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub from: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub to: Option<OffsetDateTime>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct GetPricesResponse {
+    pub price_plan_id: String,
+    pub prices: Vec<HalfHourPrice>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_prices_from_csv() {
+        let request = UploadPricesRequest::from_csv(
+            "start,unit_rate\n2020-11-29T08:00:00Z,4.5\n\n2020-11-29T08:30:00Z, -1.0\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            request.prices,
+            vec![
+                HalfHourPrice::new(datetime!(2020-11-29 08:00:00 UTC), 4.5),
+                HalfHourPrice::new(datetime!(2020-11-29 08:30:00 UTC), -1.0),
+            ]
+        );
+        assert!(request.validate().is_empty());
+    }
+
+    #[test]
+    fn test_unreadable_csv_lines_are_reported() {
+        let errors = UploadPricesRequest::from_csv(
+            "start,unit_rate\nyesterday,cheap\n2020-11-29T08:00:00Z\n",
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            errors,
+            vec![
+                FieldError::new("prices[0].start", "must be an RFC 3339 timestamp"),
+                FieldError::new("prices[0].unit_rate", "must be a number"),
+                FieldError::new("prices[1]", "must have a start and a unit_rate"),
+            ]
+        );
+        assert_eq!(
+            UploadPricesRequest::from_csv("time,price\n").unwrap_err(),
+            vec![FieldError::new("header", "must be start,unit_rate")]
+        );
+    }
+
+    #[test]
+    fn test_misaligned_prices_are_rejected() {
+        let request = UploadPricesRequest {
+            prices: vec![HalfHourPrice::new(datetime!(2020-11-29 08:10:00 UTC), 1.0)],
+        };
+
+        assert_eq!(
+            request.validate(),
+            vec![FieldError::new(
+                "prices[0].start",
+                "must be on the hour or half past"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_price_without_a_following_half_hour_is_rejected() {
+        let request = UploadPricesRequest {
+            prices: vec![HalfHourPrice::new(datetime!(9999-12-31 23:30:00 UTC), 1.0)],
+        };
+
+        assert_eq!(
+            request.validate(),
+            vec![FieldError::new(
+                "prices[0].start",
+                "must be followed by a half hour that can be represented"
+            )]
+        );
+    }
+}
@@ -1,4 +1,7 @@
 use crate::datastore::reading::ElectricityReading;
+use crate::datastore::store::ConflictPolicy;
+use crate::models::error::FieldError;
+use crate::models::validation::ValidationMode;
 use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 
@@ -7,6 +10,8 @@ pub struct GetElectricityReadingRequest {
     #[serde(with = "time::serde::rfc3339")]
     pub time: OffsetDateTime,
     pub reading: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub export: Option<f64>,
 }
 
 #[derive(Serialize, Debug, PartialEq, Copy, Clone)]
@@ -14,6 +19,8 @@ pub struct GetElectricityReadingResponse {
     #[serde(with = "time::serde::rfc3339")]
     pub time: OffsetDateTime,
     pub reading: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub export: Option<f64>,
 }
 
 impl From<&ElectricityReading> for GetElectricityReadingResponse {
@@ -21,6 +28,7 @@ impl From<&ElectricityReading> for GetElectricityReadingResponse {
         Self {
             time: electricity_reading.time,
             reading: electricity_reading.reading,
+            export: electricity_reading.export,
         }
     }
 }
@@ -30,3 +38,57 @@ pub struct CreateElectricityReadingsRequest {
     pub smart_meter_id: String,
     pub electricity_readings: Vec<GetElectricityReadingRequest>,
 }
+
+#[derive(Deserialize, Debug, Default)]
+pub struct CreateReadingsQueryParams {
+    #[serde(default)]
+    pub mode: ValidationMode,
+    #[serde(default)]
+    pub on_conflict: ConflictPolicy,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct CreateElectricityReadingsResponse {
+    pub accepted: usize,
+    pub inserted: usize,
+    pub replaced: usize,
+    pub skipped: usize,
+    pub rejected: Vec<FieldError>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct GetReadingsQueryParams {
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub from: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub to: Option<OffsetDateTime>,
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct GetElectricityReadingsPageResponse {
+    pub items: Vec<GetElectricityReadingResponse>,
+    pub next_cursor: Option<String>,
+    pub limit: usize,
+}
+
+/// Opaque position in a meter's reading history, handed to clients as `next_cursor`
+///
+/// It records the time of the last reading returned, so the next page starts
+/// strictly after it.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct ReadingsCursor(pub OffsetDateTime);
+
+impl ReadingsCursor {
+    pub fn encode(&self) -> String {
+        format!("{:x}", self.0.unix_timestamp_nanos())
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let nanos = i128::from_str_radix(cursor, 16).ok()?;
+        OffsetDateTime::from_unix_timestamp_nanos(nanos)
+            .ok()
+            .map(Self)
+    }
+}
@@ -0,0 +1,182 @@
+use crate::datastore::reading::ElectricityReading;
+use crate::models::error::FieldError;
+use crate::models::readings::CreateElectricityReadingsRequest;
+use serde::Deserialize;
+use time::{Duration, OffsetDateTime};
+
+/// How far ahead of the server clock a reading may be timestamped, to allow for
+/// smart meter clock drift
+pub const MAX_CLOCK_SKEW: Duration = Duration::minutes(5);
+
+/// What to do with a batch of readings when some of them are invalid
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationMode {
+    /// Reject the whole batch
+    #[default]
+    Strict,
+    /// Store the valid readings and report the ones that were dropped
+    Lenient,
+}
+
+/// The outcome of validating a batch of readings
+#[derive(Debug, Default, PartialEq)]
+pub struct ValidatedReadings {
+    pub valid: Vec<ElectricityReading>,
+    pub rejected: Vec<FieldError>,
+}
+
+impl CreateElectricityReadingsRequest {
+    /// Checks the request and each of its readings
+    ///
+    /// # Arguments
+    /// * `now` - The current time, used to reject readings from the future
+    ///
+    /// # Returns
+    /// The readings split into valid ones and rejection reasons for the rest, or
+    /// the problems with the request as a whole when it cannot be stored at all
+    pub fn validate(&self, now: OffsetDateTime) -> Result<ValidatedReadings, Vec<FieldError>> {
+        let mut request_errors = Vec::new();
+        if self.smart_meter_id.trim().is_empty() {
+            request_errors.push(FieldError::new("smart_meter_id", "must not be empty"));
+        }
+        if self.electricity_readings.is_empty() {
+            request_errors.push(FieldError::new(
+                "electricity_readings",
+                "must contain at least one reading",
+            ));
+        }
+        if !request_errors.is_empty() {
+            return Err(request_errors);
+        }
+
+        let mut validated = ValidatedReadings::default();
+        for (index, reading) in self.electricity_readings.iter().enumerate() {
+            let field = |name: &str| format!("electricity_readings[{index}].{name}");
+            let mut errors = Vec::new();
+            if !reading.reading.is_finite() {
+                errors.push(FieldError::new(field("reading"), "must be a finite number"));
+            } else if reading.reading < 0.0 {
+                errors.push(FieldError::new(field("reading"), "must not be negative"));
+            }
+            match reading.export {
+                Some(export) if !export.is_finite() => {
+                    errors.push(FieldError::new(field("export"), "must be a finite number"));
+                }
+                Some(export) if export < 0.0 => {
+                    errors.push(FieldError::new(field("export"), "must not be negative"));
+                }
+                _ => {}
+            }
+            if reading.time > now + MAX_CLOCK_SKEW {
+                errors.push(FieldError::new(field("time"), "must not be in the future"));
+            }
+
+            if errors.is_empty() {
+                validated.valid.push(ElectricityReading {
+                    time: reading.time,
+                    reading: reading.reading,
+                    export: reading.export,
+                });
+            } else {
+                validated.rejected.extend(errors);
+            }
+        }
+        Ok(validated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::readings::GetElectricityReadingRequest;
+    use time::macros::datetime;
+
+    const NOW: OffsetDateTime = datetime!(2020-11-29 09:00:00 UTC);
+
+    fn request(readings: Vec<(OffsetDateTime, f64)>) -> CreateElectricityReadingsRequest {
+        CreateElectricityReadingsRequest {
+            smart_meter_id: "smart-meter-0".to_string(),
+            electricity_readings: readings
+                .into_iter()
+                .map(|(time, reading)| GetElectricityReadingRequest {
+                    time,
+                    reading,
+                    export: None,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_valid_readings() {
+        let request = request(vec![(datetime!(2020-11-29 08:00:00 UTC), 1.0)]);
+
+        let validated = request.validate(NOW).unwrap();
+
+        assert_eq!(
+            validated.valid,
+            vec![ElectricityReading::new(
+                datetime!(2020-11-29 08:00:00 UTC),
+                1.0
+            )]
+        );
+        assert!(validated.rejected.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_readings_are_rejected_with_reasons() {
+        let request = request(vec![
+            (datetime!(2020-11-29 08:00:00 UTC), f64::NAN),
+            (datetime!(2020-11-29 08:01:00 UTC), -1.0),
+            (datetime!(2020-11-30 08:00:00 UTC), f64::INFINITY),
+            (datetime!(2020-11-29 08:03:00 UTC), 2.0),
+        ]);
+
+        let validated = request.validate(NOW).unwrap();
+
+        assert_eq!(validated.valid.len(), 1);
+        assert_eq!(
+            validated.rejected,
+            vec![
+                FieldError::new("electricity_readings[0].reading", "must be a finite number"),
+                FieldError::new("electricity_readings[1].reading", "must not be negative"),
+                FieldError::new("electricity_readings[2].reading", "must be a finite number"),
+                FieldError::new("electricity_readings[2].time", "must not be in the future"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_negative_export_is_rejected() {
+        let mut request = request(vec![(datetime!(2020-11-29 08:00:00 UTC), 0.0)]);
+        request.electricity_readings[0].export = Some(-1.0);
+
+        let validated = request.validate(NOW).unwrap();
+
+        assert_eq!(
+            validated.rejected,
+            vec![FieldError::new(
+                "electricity_readings[0].export",
+                "must not be negative"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_small_clock_skew_is_allowed() {
+        let request = request(vec![(NOW + Duration::minutes(1), 1.0)]);
+
+        assert!(request.validate(NOW).unwrap().rejected.is_empty());
+    }
+
+    #[test]
+    fn test_empty_request_is_rejected() {
+        let request = CreateElectricityReadingsRequest {
+            smart_meter_id: " ".to_string(),
+            electricity_readings: Vec::new(),
+        };
+
+        assert_eq!(request.validate(NOW).unwrap_err().len(), 2);
+    }
+}
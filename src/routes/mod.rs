@@ -1,22 +1,55 @@
 use axum::{
+    extract::DefaultBodyLimit,
     routing::{get, post},
     Router,
 };
 
 use crate::{
-    datastore::state,
-    handlers::{plans, readings},
+    datastore::state::AppState,
+    handlers::{accounts, bills, error, plans, prices, readings},
 };
 
-pub async fn build() -> Router {
-    let state = state::init();
+pub fn build(state: AppState) -> Router {
+    let body_limit = DefaultBodyLimit::max(state.limits.max_body_bytes);
 
     Router::new()
+        .route(
+            "/accounts",
+            get(accounts::list_accounts).post(accounts::register_account),
+        )
+        .route(
+            "/accounts/{smart_meter_id}",
+            get(accounts::get_account)
+                .put(accounts::update_account)
+                .delete(accounts::deregister_account),
+        )
+        .route(
+            "/accounts/{smart_meter_id}/plan_switches",
+            post(accounts::switch_price_plan),
+        )
         .route("/readings/create", post(readings::create_readings))
         .route(
             "/readings/read/{smart_meter_id}",
             get(readings::get_readings),
         )
+        .route(
+            "/price_plans",
+            get(plans::list_price_plans).post(plans::create_price_plan),
+        )
+        .route(
+            "/price_plans/{price_plan_id}",
+            get(plans::get_price_plan)
+                .put(plans::update_price_plan)
+                .delete(plans::retire_price_plan),
+        )
+        .route(
+            "/price_plans/{price_plan_id}/versions",
+            post(plans::change_price_plan_rates),
+        )
+        .route(
+            "/price_plans/{price_plan_id}/prices",
+            get(prices::get_half_hourly_prices).post(prices::upload_half_hourly_prices),
+        )
         .route(
             "/price_plans/compare_all/{smart_meter_id}",
             get(plans::get_price_plans),
@@ -25,12 +58,20 @@ pub async fn build() -> Router {
             "/price_plans/recommend/{smart_meter_id}",
             get(plans::get_recommended_plans),
         )
+        .route("/bills/{smart_meter_id}", get(bills::get_bill))
+        .route("/bills/{smart_meter_id}/invoice", get(bills::get_invoice))
+        .fallback(error::route_not_found)
+        .method_not_allowed_fallback(error::method_not_allowed)
+        .layer(body_limit)
         .with_state(state)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::Config;
+    use crate::datastore::catalogue::Catalogue;
+    use crate::datastore::state;
     use axum::body::to_bytes;
     use axum::{
         body::Body,
@@ -40,7 +81,7 @@ mod tests {
     use tower::ServiceExt;
 
     async fn setup() -> Router {
-        build().await
+        build(state::init(&Config::default(), Catalogue::demo()).unwrap())
     }
 
     #[tokio::test]
@@ -69,8 +110,11 @@ mod tests {
         assert_eq!(response.status(), StatusCode::OK);
 
         let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
-        let body_str = String::from_utf8(body.to_vec()).unwrap();
-        assert_eq!(body_str, "Readings created successfully");
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body,
+            json!({"accepted": 1, "inserted": 1, "replaced": 0, "skipped": 0, "rejected": []})
+        );
     }
 
     #[tokio::test]
@@ -115,14 +159,70 @@ mod tests {
         let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
         let body: Value = serde_json::from_slice(&body).unwrap();
 
+        let expected = json!({
+            "items": [
+                {
+                    "time": "2024-01-01T00:00:00Z",
+                    "reading": 1.23
+                }
+            ],
+            "next_cursor": null,
+            "limit": 100
+        });
+
+        assert_eq!(body, expected);
+    }
+
+    #[tokio::test]
+    async fn test_get_readings_in_time_range() {
+        let app = setup().await;
+
+        let create_body = json!({
+            "smart_meter_id": "smart-meter-0",
+            "electricity_readings": [
+                {"time": "2024-01-02T00:00:00Z", "reading": 2.34},
+                {"time": "2024-01-01T00:00:00Z", "reading": 1.23},
+                {"time": "2024-01-03T00:00:00Z", "reading": 3.45}
+            ]
+        });
+
+        let _ = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/readings/create")
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(serde_json::to_string(&create_body).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri("/readings/read/smart-meter-0?from=2024-01-01T12:00:00Z&to=2024-01-03T00:00:00Z")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
         let expected = json!([
             {
-                "time": "2024-01-01T00:00:00Z",
-                "reading": 1.23
+                "time": "2024-01-02T00:00:00Z",
+                "reading": 2.34
             }
         ]);
 
-        assert_eq!(body, expected);
+        assert_eq!(body["items"], expected);
     }
 
     #[tokio::test]
@@ -169,4 +269,752 @@ mod tests {
         assert!(body.get("price_plans").is_some());
         assert!(body.get("supplier_id").is_some());
     }
+
+    #[tokio::test]
+    async fn test_compare_price_plans_for_unknown_meter() {
+        let app = setup().await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri("/price_plans/compare_all/unknown-meter")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        let expected = json!({
+            "type": "/problems/unknown-smart-meter",
+            "title": "Not Found",
+            "status": 404,
+            "detail": "no account is registered for smart meter unknown-meter"
+        });
+
+        assert_eq!(body, expected);
+    }
+
+    #[tokio::test]
+    async fn test_recommend_price_plans_for_unknown_meter() {
+        let app = setup().await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri("/price_plans/recommend/unknown-meter?limit=2")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["status"], 404);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_meter_does_not_poison_later_requests() {
+        let app = setup().await;
+
+        let _ = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri("/price_plans/compare_all/unknown-meter")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri("/price_plans/compare_all/smart-meter-0")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_get_readings_with_invalid_cursor() {
+        let app = setup().await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri("/readings/read/smart-meter-0?cursor=not-a-cursor")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["detail"], "cursor is not valid");
+    }
+
+    #[tokio::test]
+    async fn test_create_readings_with_malformed_body() {
+        let app = setup().await;
+
+        let request_body = r#"{
+            "smart_meter_id": "smart-meter-0",
+            "electricity_readings": [
+                {"time": "2024-01-01T00:00:00Z", "reading": "high"}
+            ]
+        }"#;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/readings/create")
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(request_body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            response.headers()["Content-Type"],
+            "application/problem+json"
+        );
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["type"], "/problems/invalid-body");
+        assert_eq!(body["status"], 422);
+        assert_eq!(
+            body["errors"][0]["field"],
+            "electricity_readings[0].reading"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_readings_without_json_content_type() {
+        let app = setup().await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/readings/create")
+                    .body(Body::from("{}"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["status"], 415);
+    }
+
+    #[tokio::test]
+    async fn test_recommend_with_invalid_query() {
+        let app = setup().await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri("/price_plans/recommend/smart-meter-0?limit=many")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["type"], "/problems/invalid-query");
+    }
+
+    #[tokio::test]
+    async fn test_get_bill() {
+        let app = setup().await;
+
+        let create_body = json!({
+            "smart_meter_id": "smart-meter-0",
+            "electricity_readings": [
+                {"time": "2024-01-01T00:00:00Z", "reading": 1.0},
+                {"time": "2024-01-01T01:00:00Z", "reading": 1.0}
+            ]
+        });
+        let _ = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/readings/create")
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(create_body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri("/bills/smart-meter-0?period=2024-01")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["period"], "2024-01");
+        assert_eq!(body["from"], "2024-01-01T00:00:00Z");
+        assert_eq!(body["to"], "2024-02-01T00:00:00Z");
+        assert_eq!(
+            body["lines"][0],
+            json!({
+                "price_plan_id": "price-plan-0",
+                "kind": "consumption",
+                "description": "standard",
+                "quantity": 1.0,
+                "unit": "kWh",
+                "amount": {"amount": "10.00", "currency": "GBP"}
+            })
+        );
+        assert_eq!(body["total"], json!({"amount": "10.50", "currency": "GBP"}));
+
+        for period in ["January", "9999-12"] {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .method("GET")
+                        .uri(format!("/bills/smart-meter-0?period={period}"))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_invoice() {
+        let app = setup().await;
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri("/bills/smart-meter-0/invoice?period=2024-01")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()["content-type"],
+            "text/html; charset=utf-8"
+        );
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("<p class=\"customer\">Sarah<br>"));
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri("/bills/smart-meter-0/invoice?period=2024-01&format=pdf")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/pdf");
+        assert_eq!(
+            response.headers()["content-disposition"],
+            "inline; filename=\"invoice-smart-meter-0-2024-01.pdf\""
+        );
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(body.starts_with(b"%PDF-"));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_route() {
+        let app = setup().await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri("/no/such/endpoint")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["detail"], "no endpoint matches /no/such/endpoint");
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_method() {
+        let app = setup().await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("DELETE")
+                    .uri("/readings/create")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["type"], "/problems/method-not-allowed");
+    }
+
+    #[tokio::test]
+    async fn test_create_readings_with_invalid_readings() {
+        let app = setup().await;
+
+        let request_body = json!({
+            "smart_meter_id": "smart-meter-0",
+            "electricity_readings": [
+                {"time": "2024-01-01T00:00:00Z", "reading": 1.23},
+                {"time": "2024-01-01T00:01:00Z", "reading": -1.0},
+                {"time": "2999-01-01T00:00:00Z", "reading": 1.0}
+            ]
+        });
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/readings/create")
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(request_body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["type"], "/problems/invalid-readings");
+        assert_eq!(
+            body["errors"],
+            json!([
+                {"field": "electricity_readings[1].reading", "message": "must not be negative"},
+                {"field": "electricity_readings[2].time", "message": "must not be in the future"}
+            ])
+        );
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/readings/create?mode=lenient")
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(request_body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["accepted"], 1);
+        assert_eq!(body["rejected"].as_array().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_agile_prices_are_used_for_costs() {
+        let app = setup().await;
+        let send = |method: &str, uri: &str, content_type: &str, body: String| {
+            let request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("Content-Type", content_type)
+                .body(Body::from(body))
+                .unwrap();
+            app.clone().oneshot(request)
+        };
+        let read_json = |response: axum::response::Response| async {
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice::<Value>(&body).unwrap()
+        };
+        let uri = "/price_plans/price-plan-1/prices";
+        let csv = "start,unit_rate\n2024-01-01T00:00:00Z,0.5\n2024-01-01T00:30:00Z,-1.0\n";
+
+        let response = send("POST", uri, "text/csv", csv.to_string())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            read_json(response).await,
+            json!({"stored": 2, "from": "2024-01-01T00:00:00Z", "to": "2024-01-01T01:00:00Z"})
+        );
+
+        let misaligned = json!({"prices": [{"start": "2024-01-01T00:10:00Z", "unit_rate": 1.0}]});
+        let response = send("POST", uri, "application/json", misaligned.to_string())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let mistyped = json!({"prices": [{"start": "2024-01-01T00:00:00Z", "unit_rate": "1p"}]});
+        let response = send("POST", uri, "application/json", mistyped.to_string())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            read_json(response).await["errors"][0]["field"],
+            "prices[0].unit_rate"
+        );
+
+        let last = json!({"prices": [{"start": "9999-12-31T23:30:00Z", "unit_rate": 1.0}]});
+        let response = send("POST", uri, "application/json", last.to_string())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let response = send("POST", uri, "text/plain", csv.to_string())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let readings = json!({
+            "smart_meter_id": "smart-meter-0",
+            "electricity_readings": [
+                {"time": "2024-01-01T00:00:00Z", "reading": 2.0},
+                {"time": "2024-01-01T01:00:00Z", "reading": 2.0}
+            ]
+        });
+        send(
+            "POST",
+            "/readings/create",
+            "application/json",
+            readings.to_string(),
+        )
+        .await
+        .unwrap();
+        let response = send(
+            "GET",
+            "/price_plans/compare_all/smart-meter-0",
+            "application/json",
+            String::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            read_json(response).await["total_costs"]["price-plan-1"],
+            json!({"amount": "-0.50", "currency": "GBP"})
+        );
+
+        let response = send(
+            "GET",
+            &format!("{uri}?from=2024-01-01T00:30:00Z"),
+            "application/json",
+            String::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            read_json(response).await,
+            json!({
+                "price_plan_id": "price-plan-1",
+                "prices": [{"start": "2024-01-01T00:30:00Z", "unit_rate": -1.0}]
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_readings_follow_account_registration() {
+        let app = setup().await;
+        let send = |method: &str, uri: &str, body: Option<Value>| {
+            let request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("Content-Type", "application/json")
+                .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+                .unwrap();
+            app.clone().oneshot(request)
+        };
+        let readings = json!({
+            "smart_meter_id": "smart-meter-5",
+            "electricity_readings": [
+                {"time": "2024-01-01T00:00:00Z", "reading": 1.23}
+            ]
+        });
+
+        let response = send("POST", "/readings/create", Some(readings.clone()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let account = json!({
+            "smart_meter_id": "smart-meter-5",
+            "user": "Jo",
+            "price_plan_id": "price-plan-1"
+        });
+        let response = send("POST", "/accounts", Some(account.clone()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = send("POST", "/readings/create", Some(readings.clone()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = send("GET", "/accounts/smart-meter-5", None).await.unwrap();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["price_plan_id"], account["price_plan_id"]);
+        assert_eq!(
+            body["price_plan_history"],
+            json!([{"price_plan_id": "price-plan-1", "effective_from": null}])
+        );
+
+        let response = send("DELETE", "/accounts/smart-meter-5", None)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let response = send("POST", "/readings/create", Some(readings))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_manage_price_plans() {
+        let app = setup().await;
+        let send = |method: &str, uri: &str, body: Option<Value>| {
+            let request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("Content-Type", "application/json")
+                .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+                .unwrap();
+            app.clone().oneshot(request)
+        };
+        let plan = json!({
+            "id": "price-plan-3",
+            "name": "Night Owl",
+            "currency": "EUR",
+            "vat_category": "standard",
+            "unit_rate": 4.0,
+            "standing_charge": 25.0,
+            "export_rate": 0.0,
+            "multipliers": {"Sunday": 0.5},
+            "utc_offset": "+00:00",
+            "time_zone": null,
+            "rate_bands": [{"name": "night", "start": "23:00", "end": "07:00", "unit_rate": 1.0}],
+            "tiers": [],
+            "retired": false,
+            "valid_from": null,
+            "previous_versions": []
+        });
+
+        let response = send("POST", "/price_plans", Some(plan.clone()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = send("POST", "/price_plans", Some(plan.clone()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let mut updated = plan.clone();
+        updated["unit_rate"] = json!(3.5);
+        let response = send("PUT", "/price_plans/price-plan-3", Some(updated.clone()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let rates = json!({
+            "valid_from": "2024-01-01T00:00:00Z",
+            "unit_rate": 5.0,
+            "standing_charge": 30.0,
+            "export_rate": 4.0
+        });
+        let response = send("POST", "/price_plans/price-plan-3/versions", Some(rates))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        updated["previous_versions"] = json!([{
+            "valid_from": null,
+            "valid_to": "2024-01-01T00:00:00Z",
+            "unit_rate": 3.5,
+            "standing_charge": 25.0,
+            "export_rate": 0.0,
+            "multipliers": {"Sunday": 0.5},
+            "rate_bands": [{"name": "night", "start": "23:00", "end": "07:00", "unit_rate": 1.0}],
+            "tiers": []
+        }]);
+        updated["valid_from"] = json!("2024-01-01T00:00:00Z");
+        updated["unit_rate"] = json!(5.0);
+        updated["standing_charge"] = json!(30.0);
+        updated["export_rate"] = json!(4.0);
+        updated["multipliers"] = json!({});
+        updated["rate_bands"] = json!([]);
+
+        let response = send("DELETE", "/price_plans/price-plan-3", None)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = send("GET", "/price_plans/price-plan-3", None)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        updated["retired"] = json!(true);
+        assert_eq!(body, updated);
+
+        let response = send("GET", "/price_plans", None).await.unwrap();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body.as_array().unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_create_invalid_price_plan() {
+        let app = setup().await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/price_plans")
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(
+                        json!({"id": "price-plan-3", "name": "Bad", "unit_rate": -1.0}).to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["type"], "/problems/invalid-price-plan");
+        assert_eq!(
+            body["errors"],
+            json!([{"field": "unit_rate", "message": "must be a non-negative number"}])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_unknown_price_plan() {
+        let app = setup().await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri("/price_plans/price-plan-9")
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(
+                        json!({"id": "price-plan-9", "name": "Missing", "unit_rate": 1.0})
+                            .to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_create_readings_with_empty_batch() {
+        let app = setup().await;
+
+        let request_body = json!({
+            "smart_meter_id": "smart-meter-0",
+            "electricity_readings": []
+        });
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/readings/create?mode=lenient")
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(request_body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
 }